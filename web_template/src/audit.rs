@@ -0,0 +1,67 @@
+use crate::events::ForexPairEvent;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+pub const DEFAULT_AUDIT_CAPACITY: usize = 10_000;
+
+// In-memory log of recent pair events, oldest entries drop off once full
+pub struct AuditLog {
+    entries: Mutex<VecDeque<ForexPairEvent>>,
+    capacity: usize,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_AUDIT_CAPACITY)
+    }
+}
+
+impl AuditLog {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    pub fn record(&self, event: ForexPairEvent) {
+        let mut entries: std::sync::MutexGuard<VecDeque<ForexPairEvent>> =
+            self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(event);
+    }
+
+    // Oldest first
+    pub fn entries(&self) -> Vec<ForexPairEvent> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::forex_pair;
+
+    #[test]
+    fn tests_audit_log_drops_oldest_when_full() {
+        let log: AuditLog = AuditLog::with_capacity(2);
+        for id in 1..=3 {
+            log.record(ForexPairEvent::created(
+                "anonymous",
+                &forex_pair(id, "EUR/USD", 1.0),
+            ));
+        }
+        let ids: Vec<u64> = log.entries().iter().map(|event| event.pair_id).collect();
+        assert_eq!(ids, vec![2, 3]);
+    }
+}
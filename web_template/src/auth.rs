@@ -0,0 +1,50 @@
+use crate::error::AppError;
+use crate::state::AppState;
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use std::future::{ready, Ready};
+
+// Extractor guarding admin handlers: requires `Authorization: Bearer <ADMIN_TOKEN>`
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authorize(req))
+    }
+}
+
+// Who performed a change, as recorded in events: "admin" for a valid admin token
+pub struct Actor(pub String);
+
+impl FromRequest for Actor {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let actor: &str = match authorize(req) {
+            Ok(Admin) => "admin",
+            Err(_) => "anonymous",
+        };
+        ready(Ok(Actor(actor.to_string())))
+    }
+}
+
+fn authorize(req: &HttpRequest) -> Result<Admin, AppError> {
+    let expected: &str = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|state| state.config.admin_token.as_deref())
+        .ok_or_else(|| AppError::Forbidden("admin access is disabled".to_string()))?;
+
+    let provided: Option<&str> = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if token == expected => Ok(Admin),
+        _ => Err(AppError::Unauthorized),
+    }
+}
@@ -0,0 +1,197 @@
+use crate::config::Config;
+use crate::database::{Database, ForexPair};
+use crate::events::ForexPairEvent;
+use crate::persistence::Snapshot;
+use crate::provider::PriceProvider;
+use crate::state::AppState;
+use std::time::Duration;
+
+// Actor on the created events, so the audit log shows where the records came from
+const BOOTSTRAP_ACTOR: &str = "bootstrap";
+// Per pair, so an unresponsive provider cannot hold up startup for long
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Fill an empty database with config.bootstrap_pairs priced by the provider, so a fresh
+// deploy does not start empty. Never fails: pairs the provider cannot price are logged and
+// skipped, and with the provider down the service simply starts empty. Returns how many
+// records were inserted.
+pub async fn bootstrap(app_state: &AppState) -> usize {
+    let config: &Config = &app_state.config;
+    if !config.bootstrap_enabled || config.bootstrap_pairs.is_empty() {
+        return 0;
+    }
+    if !app_state.db.lock().unwrap().is_empty() {
+        return 0;
+    }
+    let Some(provider) = app_state.provider.clone() else {
+        tracing::warn!("bootstrap: no price provider configured, starting empty");
+        return 0;
+    };
+
+    let quotes: Vec<(String, f64)> = fetch_prices(app_state, provider.as_ref()).await;
+    if quotes.is_empty() {
+        tracing::warn!("bootstrap: no prices fetched, starting empty");
+        return 0;
+    }
+
+    let (inserted, snapshot): (Vec<ForexPair>, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        if !db.is_empty() {
+            return 0;
+        }
+        let mut inserted: Vec<ForexPair> = Vec::new();
+        for (pair, price) in quotes {
+            let mut forex_pair: ForexPair = ForexPair {
+                id: 0,
+                pair,
+                price,
+                bid: None,
+                ask: None,
+                created_at: Some(chrono::Utc::now()),
+                extra_fields: None,
+            };
+            if let Err(err) = forex_pair.validate(config.max_spread_pct) {
+                tracing::warn!(pair = %forex_pair.pair, error = %err, "bootstrap: skipped");
+                continue;
+            }
+            forex_pair.id = db.allocate_id();
+            db.insert(forex_pair.clone());
+            inserted.push(forex_pair);
+        }
+        match app_state.snapshot(&db) {
+            Ok(snapshot) => (inserted, snapshot),
+            Err(err) => {
+                tracing::error!(error = %err, "bootstrap: could not snapshot the database");
+                return inserted.len();
+            }
+        }
+    };
+    // The records stay live when the save fails, the next write saves them
+    if let Err(err) = app_state.persist(snapshot).await {
+        tracing::error!(error = %err, "bootstrap: could not save the database");
+    }
+    for forex_pair in &inserted {
+        app_state.publish(ForexPairEvent::created(BOOTSTRAP_ACTOR, forex_pair));
+    }
+    tracing::info!(
+        count = inserted.len(),
+        "bootstrap: populated empty database"
+    );
+    inserted.len()
+}
+
+// (normalised pair, price) for every configured pair the provider could price, going
+// through the circuit breaker like refresh does
+async fn fetch_prices(app_state: &AppState, provider: &dyn PriceProvider) -> Vec<(String, f64)> {
+    let mut quotes: Vec<(String, f64)> = Vec::new();
+    for raw in &app_state.config.bootstrap_pairs {
+        let pair: String = match ForexPair::normalize_pair(raw) {
+            Ok(pair) => pair,
+            Err(err) => {
+                tracing::warn!(error = %err, "bootstrap: skipped");
+                continue;
+            }
+        };
+        if quotes.iter().any(|(seen, _)| *seen == pair) {
+            continue;
+        }
+        let Ok(call) = app_state.provider_breaker.try_call() else {
+            tracing::warn!("bootstrap: price provider circuit is open, giving up");
+            break;
+        };
+        match tokio::time::timeout(FETCH_TIMEOUT, provider.fetch_price(&pair)).await {
+            Ok(Ok(price)) => {
+                call.succeeded();
+                quotes.push((pair, price));
+            }
+            Ok(Err(err)) => {
+                call.failed(err.retry_after());
+                tracing::warn!(%pair, error = %err, "bootstrap: could not fetch price");
+            }
+            Err(_) => {
+                call.failed(None);
+                tracing::warn!(%pair, "bootstrap: price provider timed out");
+            }
+        }
+    }
+    quotes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ProviderError;
+    use crate::test_support::{forex_pair, temp_database};
+    use async_trait::async_trait;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    // Quotes every pair except GBP/JPY; with `down` it fails everything
+    struct MockProvider {
+        down: bool,
+    }
+
+    #[async_trait]
+    impl PriceProvider for MockProvider {
+        async fn fetch_price(&self, pair: &str) -> Result<f64, ProviderError> {
+            match pair {
+                _ if self.down => Err(ProviderError::Unavailable("connection refused".into())),
+                "GBP/JPY" => Err(ProviderError::InvalidResponse("no quote".into())),
+                "USD/JPY" => Ok(151.2),
+                _ => Ok(1.08),
+            }
+        }
+    }
+
+    fn bootstrap_config() -> Config {
+        Config {
+            bootstrap_enabled: true,
+            bootstrap_pairs: vec![
+                "eur/usd".to_string(),
+                "USDJPY".to_string(),
+                "GBP/JPY".to_string(),
+                "not a pair".to_string(),
+            ],
+            ..Config::default()
+        }
+    }
+
+    fn state(db: Database, config: Config, down: bool) -> AppState {
+        AppState::new(db, config).with_provider(Arc::new(MockProvider { down }))
+    }
+
+    #[actix_web::test]
+    async fn tests_bootstrap_populates_empty_database() {
+        let db: Database = temp_database("bootstrap");
+        let path: PathBuf = db.database_path().to_path_buf();
+        let app_state: AppState = state(db, bootstrap_config(), false);
+
+        assert_eq!(bootstrap(&app_state).await, 2);
+        let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().price, 1.08);
+        assert_eq!(db.find_by_pair("USD/JPY").unwrap().price, 151.2);
+        assert!(db.find_by_pair("GBP/JPY").is_none());
+        assert_eq!(Database::load_from_file(&path).unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn tests_bootstrap_skips_populated_disabled_or_unreachable() {
+        let mut db: Database = temp_database("bootstrap_populated");
+        db.insert(forex_pair(1, "AUD/USD", 0.66));
+        let app_state: AppState = state(db, bootstrap_config(), false);
+        assert_eq!(bootstrap(&app_state).await, 0);
+        assert_eq!(app_state.db.lock().unwrap().len(), 1);
+
+        let disabled: Config = Config {
+            bootstrap_enabled: false,
+            ..bootstrap_config()
+        };
+        let app_state: AppState = state(temp_database("bootstrap_disabled"), disabled, false);
+        assert_eq!(bootstrap(&app_state).await, 0);
+
+        // Provider down: startup carries on with an empty database
+        let app_state: AppState = state(temp_database("bootstrap_down"), bootstrap_config(), true);
+        assert_eq!(bootstrap(&app_state).await, 0);
+        assert!(app_state.db.lock().unwrap().is_empty());
+    }
+}
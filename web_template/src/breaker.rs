@@ -0,0 +1,242 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+// Longest an upstream Retry-After may keep the breaker open
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(86_400);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    // Cooldown over, one trial call is allowed through to test recovery
+    HalfOpen,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BreakerStatus {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    // Seconds until an open breaker half-opens
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+// Guards calls to an upstream: opens after `failure_threshold` failures in a row, or at once
+// when the upstream sends Retry-After, and rejects calls until the cooldown has passed
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    inner: Mutex<Inner>,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN)
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                open_until: None,
+                trial_in_flight: false,
+            }),
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+        }
+    }
+
+    // Ok to go ahead with the call, or Err with how long to wait
+    pub fn try_call(&self) -> Result<BreakerCall<'_>, Duration> {
+        let now: Instant = Instant::now();
+        let mut inner: std::sync::MutexGuard<Inner> = self.inner.lock().unwrap();
+        match inner.state {
+            BreakerState::Closed => Ok(BreakerCall::new(self)),
+            BreakerState::Open => {
+                let open_until: Instant = inner.open_until.unwrap_or(now);
+                if now < open_until {
+                    return Err(open_until - now);
+                }
+                inner.state = BreakerState::HalfOpen;
+                inner.trial_in_flight = true;
+                Ok(BreakerCall::new(self))
+            }
+            // Only the one trial call; everyone else waits for its outcome
+            BreakerState::HalfOpen if inner.trial_in_flight => Err(Duration::from_secs(1)),
+            BreakerState::HalfOpen => {
+                inner.trial_in_flight = true;
+                Ok(BreakerCall::new(self))
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        let mut inner: std::sync::MutexGuard<Inner> = self.inner.lock().unwrap();
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+        inner.open_until = None;
+        inner.trial_in_flight = false;
+    }
+
+    // retry_after comes from the upstream and always opens the breaker for at least that long
+    fn record_failure(&self, retry_after: Option<Duration>) {
+        let now: Instant = Instant::now();
+        let mut inner: std::sync::MutexGuard<Inner> = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        inner.trial_in_flight = false;
+
+        let trips: bool = retry_after.is_some()
+            || inner.state == BreakerState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold;
+        if trips {
+            inner.state = BreakerState::Open;
+            let wait: Duration = retry_after
+                .unwrap_or(self.cooldown)
+                .min(MAX_RETRY_AFTER)
+                .max(self.cooldown);
+            inner.open_until = Some(now.checked_add(wait).unwrap_or(now + MAX_RETRY_AFTER));
+        }
+    }
+
+    // The caller went away mid-call: free the trial slot for the next caller without counting
+    // anything against the upstream
+    fn abandon(&self) {
+        self.inner.lock().unwrap().trial_in_flight = false;
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let now: Instant = Instant::now();
+        let inner: std::sync::MutexGuard<Inner> = self.inner.lock().unwrap();
+        let remaining: Option<Duration> = match inner.state {
+            BreakerState::Open => inner
+                .open_until
+                .map(|until| until.saturating_duration_since(now)),
+            _ => None,
+        };
+        BreakerStatus {
+            // Report an expired cooldown as half-open even before the next call flips it
+            state: match remaining {
+                Some(remaining) if remaining.is_zero() => BreakerState::HalfOpen,
+                _ => inner.state,
+            },
+            consecutive_failures: inner.consecutive_failures,
+            retry_after: remaining.map(|remaining| remaining.as_secs_f64().ceil() as u64),
+        }
+    }
+}
+
+// A call the breaker let through. Report how it went with succeeded or failed; dropped without
+// either (the request was cancelled mid-call) it only hands a half-open trial back.
+#[must_use]
+#[derive(Debug)]
+pub struct BreakerCall<'a> {
+    breaker: &'a CircuitBreaker,
+    reported: bool,
+}
+
+impl<'a> BreakerCall<'a> {
+    fn new(breaker: &'a CircuitBreaker) -> Self {
+        Self {
+            breaker,
+            reported: false,
+        }
+    }
+
+    pub fn succeeded(mut self) {
+        self.reported = true;
+        self.breaker.record_success();
+    }
+
+    pub fn failed(mut self, retry_after: Option<Duration>) {
+        self.reported = true;
+        self.breaker.record_failure(retry_after);
+    }
+}
+
+impl Drop for BreakerCall<'_> {
+    fn drop(&mut self) {
+        if !self.reported {
+            self.breaker.abandon();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_breaker_opens_half_opens_and_closes() {
+        let breaker: CircuitBreaker = CircuitBreaker::new(2, Duration::from_millis(50));
+        breaker.try_call().unwrap().failed(None);
+        assert_eq!(breaker.status().state, BreakerState::Closed);
+        breaker.try_call().unwrap().failed(None);
+        assert_eq!(breaker.status().state, BreakerState::Open);
+        assert!(breaker.try_call().is_err());
+
+        std::thread::sleep(Duration::from_millis(60));
+        let trial: BreakerCall = breaker.try_call().unwrap();
+        assert_eq!(breaker.status().state, BreakerState::HalfOpen);
+        // A second caller is held back while the trial runs
+        assert!(breaker.try_call().is_err());
+
+        // A failed trial reopens straight away
+        trial.failed(None);
+        assert_eq!(breaker.status().state, BreakerState::Open);
+        std::thread::sleep(Duration::from_millis(60));
+        breaker.try_call().unwrap().succeeded();
+        assert_eq!(breaker.status().state, BreakerState::Closed);
+        assert_eq!(breaker.status().consecutive_failures, 0);
+    }
+
+    #[test]
+    fn tests_dropped_trial_frees_the_slot() {
+        let breaker: CircuitBreaker = CircuitBreaker::new(1, Duration::from_millis(10));
+        breaker.try_call().unwrap().failed(None);
+        std::thread::sleep(Duration::from_millis(20));
+
+        drop(breaker.try_call().unwrap());
+        // Nothing was counted, and the next caller gets the trial instead of waiting forever
+        assert_eq!(breaker.status().state, BreakerState::HalfOpen);
+        assert_eq!(breaker.status().consecutive_failures, 1);
+        breaker.try_call().unwrap().succeeded();
+        assert_eq!(breaker.status().state, BreakerState::Closed);
+    }
+
+    #[test]
+    fn tests_retry_after_opens_immediately() {
+        let breaker: CircuitBreaker = CircuitBreaker::new(5, Duration::from_millis(10));
+        breaker.record_failure(Some(Duration::from_secs(120)));
+        let status: BreakerStatus = breaker.status();
+        assert_eq!(status.state, BreakerState::Open);
+        assert_eq!(status.retry_after, Some(120));
+        assert!(breaker.try_call().unwrap_err() > Duration::from_secs(119));
+    }
+
+    #[test]
+    fn tests_huge_retry_after_is_capped() {
+        let breaker: CircuitBreaker = CircuitBreaker::default();
+        breaker.record_failure(Some(Duration::MAX));
+        let status: BreakerStatus = breaker.status();
+        assert_eq!(status.state, BreakerState::Open);
+        assert_eq!(status.retry_after, Some(MAX_RETRY_AFTER.as_secs()));
+        // The lock is still usable afterwards
+        breaker.record_success();
+        assert_eq!(breaker.status().state, BreakerState::Closed);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::breaker::{DEFAULT_COOLDOWN, DEFAULT_FAILURE_THRESHOLD};
+use crate::database::{IdStrategy, DEFAULT_DATABASE_PATH};
+use crate::maintenance::{parse_windows, MaintenanceWindow};
+use crate::persistence::DEFAULT_SAVE_MAX_ATTEMPTS;
+use crate::validation::DEFAULT_MAX_SPREAD_PCT;
+use crate::webhook::DEFAULT_WEBHOOK_TIMEOUT;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const DEFAULT_WRITE_CONCURRENCY: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Bearer token required by the /admin routes. Admin routes are refused when unset.
+    pub admin_token: Option<String>,
+    // When false the matching routes are not registered at all and answer 404
+    pub enable_delete: bool,
+    pub enable_admin: bool,
+    pub database_path: PathBuf,
+    pub id_strategy: IdStrategy,
+    // Widest bid/ask spread accepted on writes, as a percentage of mid
+    pub max_spread_pct: Decimal,
+    // Scheduled read-only windows, see maintenance::parse_windows for the format
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    // Permits in AppState::write_semaphore
+    pub write_concurrency: usize,
+    // Attempts per save before the error reaches the client
+    pub save_max_attempts: u32,
+    // Upstream queried by POST /forex_pair/{id}/refresh; refresh answers 503 when unset
+    pub provider_url: Option<String>,
+    // Consecutive provider failures before the circuit breaker opens, and how long it stays open
+    pub breaker_failure_threshold: u32,
+    pub breaker_cooldown: Duration,
+    // Let admins see the raw provider response with POST /forex_pair/{id}/refresh?debug=true
+    pub provider_debug: bool,
+    // Locale used for formatted_price on GET responses when the request has no ?locale
+    pub default_locale: Option<String>,
+    // Static headers added to every response that does not already set them
+    pub custom_headers: HashMap<String, String>,
+    // Answer errors with an HTML page when the client prefers text/html
+    pub html_errors: bool,
+    // Check the loaded database for inconsistencies (startup and reload) and repair them
+    pub repair_on_load: bool,
+    // Where startup and shutdown events are POSTed; nothing is sent when unset or disabled
+    pub webhook_url: Option<String>,
+    pub webhook_enabled: bool,
+    pub webhook_timeout: Duration,
+    // PKCS#8 PEM Ed25519 private key; when set every response carries X-Response-Signature
+    pub response_signing_key: Option<PathBuf>,
+    // Answer GET /forex_pairs/by_pair for QUOTE/BASE from a stored BASE/QUOTE record
+    pub serve_inverse_pairs: bool,
+    // Refuse pair bodies with fields ForexPair does not declare instead of keeping them as
+    // extra fields. Off by default for clients that rely on forward-compatible leniency.
+    pub strict_fields: bool,
+    // Wrap successful responses in {"data", "meta"}; ?envelope= overrides it per request
+    pub response_envelope: bool,
+    // Pairs priced from the provider and inserted when the database is empty at startup
+    pub bootstrap_enabled: bool,
+    pub bootstrap_pairs: Vec<String>,
+    // Debug-only behaviour such as debug_delay_ms is ignored unless this is on
+    pub debug_mode: bool,
+    // Sleep this long before handling every request, to exercise client loading states
+    pub debug_delay_ms: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            admin_token: None,
+            enable_delete: true,
+            enable_admin: true,
+            database_path: PathBuf::from(DEFAULT_DATABASE_PATH),
+            id_strategy: IdStrategy::default(),
+            max_spread_pct: DEFAULT_MAX_SPREAD_PCT,
+            maintenance_windows: Vec::new(),
+            write_concurrency: DEFAULT_WRITE_CONCURRENCY,
+            save_max_attempts: DEFAULT_SAVE_MAX_ATTEMPTS,
+            provider_url: None,
+            breaker_failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            breaker_cooldown: DEFAULT_COOLDOWN,
+            provider_debug: false,
+            default_locale: None,
+            custom_headers: HashMap::new(),
+            html_errors: true,
+            repair_on_load: true,
+            webhook_url: None,
+            webhook_enabled: true,
+            webhook_timeout: DEFAULT_WEBHOOK_TIMEOUT,
+            response_signing_key: None,
+            serve_inverse_pairs: false,
+            strict_fields: false,
+            response_envelope: false,
+            bootstrap_enabled: false,
+            bootstrap_pairs: Vec::new(),
+            debug_mode: false,
+            debug_delay_ms: None,
+        }
+    }
+}
+
+impl Config {
+    // The artificial per-request delay, only ever in debug mode
+    pub fn debug_delay(&self) -> Option<Duration> {
+        self.debug_delay_ms
+            .filter(|_| self.debug_mode)
+            .map(Duration::from_millis)
+    }
+
+    // Read configuration from the environment (call dotenv first to pick up .env)
+    pub fn from_env() -> Self {
+        let defaults: Config = Config::default();
+        Self {
+            admin_token: env::var("ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            enable_delete: env_flag("ENABLE_DELETE", defaults.enable_delete),
+            enable_admin: env_flag("ENABLE_ADMIN", defaults.enable_admin),
+            database_path: env::var("DATABASE_PATH")
+                .map(PathBuf::from)
+                .unwrap_or(defaults.database_path),
+            id_strategy: env::var("ID_STRATEGY")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.id_strategy),
+            max_spread_pct: env::var("MAX_SPREAD_PCT")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.max_spread_pct),
+            maintenance_windows: match env::var("MAINTENANCE_WINDOWS") {
+                Ok(raw) => parse_windows(&raw).unwrap_or_else(|err| {
+                    // Refuse to start rather than silently skip planned maintenance
+                    panic!("invalid MAINTENANCE_WINDOWS: {}", err)
+                }),
+                Err(_) => defaults.maintenance_windows,
+            },
+            write_concurrency: env::var("WRITE_CONCURRENCY")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|permits: &usize| *permits > 0)
+                .unwrap_or(defaults.write_concurrency),
+            save_max_attempts: env::var("SAVE_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|attempts: &u32| *attempts > 0)
+                .unwrap_or(defaults.save_max_attempts),
+            provider_url: env::var("PROVIDER_URL").ok().filter(|url| !url.is_empty()),
+            breaker_failure_threshold: env::var("BREAKER_FAILURE_THRESHOLD")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.breaker_failure_threshold),
+            breaker_cooldown: env::var("BREAKER_COOLDOWN_SECS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.breaker_cooldown),
+            provider_debug: env_flag("PROVIDER_DEBUG", defaults.provider_debug),
+            default_locale: env::var("DEFAULT_LOCALE")
+                .ok()
+                .filter(|locale| !locale.is_empty()),
+            // A JSON object, e.g. {"X-Frame-Options": "DENY"}
+            custom_headers: match env::var("CUSTOM_HEADERS") {
+                Ok(raw) => serde_json::from_str(&raw)
+                    .unwrap_or_else(|err| panic!("invalid CUSTOM_HEADERS: {}", err)),
+                Err(_) => defaults.custom_headers,
+            },
+            html_errors: env_flag("HTML_ERRORS", defaults.html_errors),
+            repair_on_load: env_flag("REPAIR_ON_LOAD", defaults.repair_on_load),
+            webhook_url: env::var("WEBHOOK_URL").ok().filter(|url| !url.is_empty()),
+            webhook_enabled: env_flag("WEBHOOK_ENABLED", defaults.webhook_enabled),
+            webhook_timeout: env::var("WEBHOOK_TIMEOUT_MS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.webhook_timeout),
+            response_signing_key: env::var("RESPONSE_SIGNING_KEY")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            serve_inverse_pairs: env_flag("SERVE_INVERSE_PAIRS", defaults.serve_inverse_pairs),
+            strict_fields: env_flag("STRICT_FIELDS", defaults.strict_fields),
+            response_envelope: env_flag("RESPONSE_ENVELOPE", defaults.response_envelope),
+            bootstrap_enabled: env_flag("BOOTSTRAP_ENABLED", defaults.bootstrap_enabled),
+            // Comma separated, e.g. EUR/USD,GBP/USD,USD/JPY
+            bootstrap_pairs: match env::var("BOOTSTRAP_PAIRS") {
+                Ok(raw) => raw
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|pair| !pair.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                Err(_) => defaults.bootstrap_pairs,
+            },
+            debug_mode: env_flag("DEBUG_MODE", defaults.debug_mode),
+            debug_delay_ms: env::var("DEBUG_DELAY_MS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|delay_ms: &u64| *delay_ms > 0),
+        }
+    }
+}
+
+// Parse a boolean env var, falling back to the default when unset or unrecognised
+fn env_flag(name: &str, default: bool) -> bool {
+    match env::var(name).map(|value| value.trim().to_ascii_lowercase()) {
+        Ok(value) if matches!(value.as_str(), "1" | "true" | "yes" | "on") => true,
+        Ok(value) if matches!(value.as_str(), "0" | "false" | "no" | "off") => false,
+        _ => default,
+    }
+}
@@ -0,0 +1,196 @@
+use crate::database::{parse_price, ForexPair};
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    NotAnObject,
+    MissingField(&'static str),
+    WrongType {
+        field: &'static str,
+        expected: &'static str,
+        found: String,
+    },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnObject => write!(f, "expected a JSON object for a forex pair"),
+            Self::MissingField(field) => write!(f, "missing field {:?}", field),
+            Self::WrongType {
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "field {:?} should be {} but was {}",
+                field, expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+// serde_json's blanket `impl<T: Into<Value>> From<Vec<T>> for Value` builds on this one,
+// so `Value::from(Vec<ForexPair>)` yields a JSON array (the orphan rule forbids a direct impl)
+impl From<ForexPair> for Value {
+    fn from(forex_pair: ForexPair) -> Self {
+        serde_json::to_value(forex_pair).expect("ForexPair always serialises to JSON")
+    }
+}
+
+impl TryFrom<Value> for ForexPair {
+    type Error = ConversionError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let mut fields: Map<String, Value> = match value {
+            Value::Object(fields) => fields,
+            _ => return Err(ConversionError::NotAnObject),
+        };
+
+        // Matches serde: a missing id means "assign one"
+        let id: u64 = match fields.remove("id") {
+            None => 0,
+            Some(value) => value
+                .as_u64()
+                .ok_or_else(|| wrong_type("id", "an unsigned integer", &value))?,
+        };
+        let pair: String = match fields.remove("pair") {
+            None => return Err(ConversionError::MissingField("pair")),
+            Some(Value::String(pair)) => pair,
+            Some(value) => return Err(wrong_type("pair", "a string", &value)),
+        };
+        // Same coercion as the serde path: numeric strings are accepted
+        let price: f64 = match fields.remove("price") {
+            None => return Err(ConversionError::MissingField("price")),
+            Some(Value::String(raw)) => parse_price(&raw)
+                .map_err(|_| wrong_type("price", "a number", &Value::String(raw)))?,
+            Some(value) => value
+                .as_f64()
+                .ok_or_else(|| wrong_type("price", "a number", &value))?,
+        };
+        let bid: Option<f64> = optional_number(&mut fields, "bid")?;
+        let ask: Option<f64> = optional_number(&mut fields, "ask")?;
+        let created_at: Option<DateTime<Utc>> = match fields.remove("created_at") {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(
+                serde_json::from_value(value.clone())
+                    .map_err(|_| wrong_type("created_at", "an RFC 3339 timestamp", &value))?,
+            ),
+        };
+
+        Ok(ForexPair {
+            id,
+            pair,
+            price,
+            bid,
+            ask,
+            created_at,
+            extra_fields: if fields.is_empty() {
+                None
+            } else {
+                Some(fields)
+            },
+        })
+    }
+}
+
+fn optional_number(
+    fields: &mut Map<String, Value>,
+    field: &'static str,
+) -> Result<Option<f64>, ConversionError> {
+    match fields.remove(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| wrong_type(field, "a number", &value)),
+    }
+}
+
+fn wrong_type(field: &'static str, expected: &'static str, value: &Value) -> ConversionError {
+    let found: &str = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    };
+    ConversionError::WrongType {
+        field,
+        expected,
+        found: found.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::forex_pair;
+    use serde_json::json;
+
+    #[test]
+    fn tests_forex_pair_value_round_trip() {
+        let mut original: ForexPair = forex_pair(3, "USD/JPY", 151.2);
+        original.bid = Some(151.19);
+        original.ask = Some(151.21);
+        let mut extra: Map<String, Value> = Map::new();
+        extra.insert("venue".to_string(), json!("LMAX"));
+        original.extra_fields = Some(extra);
+
+        let value: Value = Value::from(original.clone());
+        assert_eq!(
+            value,
+            json!({
+                "id": 3, "pair": "USD/JPY", "price": 151.2,
+                "bid": 151.19, "ask": 151.21, "venue": "LMAX"
+            })
+        );
+
+        let back: ForexPair = ForexPair::try_from(value).unwrap();
+        assert_eq!(back.id, original.id);
+        assert_eq!(back.pair, original.pair);
+        assert_eq!(back.price, original.price);
+        assert_eq!((back.bid, back.ask), (original.bid, original.ask));
+        assert_eq!(back.extra_fields, original.extra_fields);
+    }
+
+    #[test]
+    fn tests_vec_converts_to_json_array() {
+        let value: Value = Value::from(vec![
+            forex_pair(1, "EUR/USD", 1.08),
+            forex_pair(2, "GBP/USD", 1.27),
+        ]);
+        assert_eq!(value.as_array().unwrap().len(), 2);
+        assert_eq!(value[1]["pair"], "GBP/USD");
+    }
+
+    #[test]
+    fn tests_try_from_reports_descriptive_errors() {
+        assert_eq!(
+            ForexPair::try_from(json!([1, 2])).unwrap_err(),
+            ConversionError::NotAnObject
+        );
+        assert_eq!(
+            ForexPair::try_from(json!({ "id": 1, "price": 1.0 })).unwrap_err(),
+            ConversionError::MissingField("pair")
+        );
+
+        let err: ConversionError =
+            ForexPair::try_from(json!({ "id": 1, "pair": "EUR/USD", "price": "high" }))
+                .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "field \"price\" should be a number but was a string"
+        );
+
+        assert!(matches!(
+            ForexPair::try_from(json!({ "id": -4, "pair": "EUR/USD", "price": 1.0 })),
+            Err(ConversionError::WrongType { field: "id", .. })
+        ));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::database::{Database, ForexPair};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+// Prices at or below this are treated as unusable when inverting
+const MIN_INVERTIBLE_PRICE: f64 = 1e-12;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RateSource {
+    Identity,
+    Direct,
+    Inverse,
+    Triangulated { via: String },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Rate {
+    pub rate: f64,
+    pub source: RateSource,
+    // Ids of the stored pairs the rate was computed from, in order
+    pub path: Vec<u64>,
+}
+
+impl Rate {
+    pub fn is_derived(&self) -> bool {
+        !matches!(self.source, RateSource::Direct | RateSource::Identity)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateError {
+    InvalidCurrency(String),
+    NoRoute { base: String, quote: String },
+}
+
+impl fmt::Display for RateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCurrency(code) => write!(f, "{:?} is not a 3-letter currency code", code),
+            Self::NoRoute { base, quote } => {
+                write!(
+                    f,
+                    "no direct, inverse or triangulated rate for {}/{}",
+                    base, quote
+                )
+            }
+        }
+    }
+}
+
+// Canonical currency code, e.g. " eur" -> "EUR"
+pub fn normalize_currency(raw: &str) -> Result<String, RateError> {
+    let code: String = raw.trim().to_ascii_uppercase();
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase()) {
+        Ok(code)
+    } else {
+        Err(RateError::InvalidCurrency(raw.to_string()))
+    }
+}
+
+// Rate for one unit of base in quote: direct, then inverse, then via one intermediate currency
+pub fn convert_rate(db: &Database, base: &str, quote: &str) -> Result<Rate, RateError> {
+    let base: String = normalize_currency(base)?;
+    let quote: String = normalize_currency(quote)?;
+
+    if base == quote {
+        return Ok(Rate {
+            rate: 1.0,
+            source: RateSource::Identity,
+            path: vec![],
+        });
+    }
+
+    if let Some(forex_pair) = db.find_by_pair(&format!("{}/{}", base, quote)) {
+        return Ok(Rate {
+            rate: forex_pair.price,
+            source: RateSource::Direct,
+            path: vec![forex_pair.id],
+        });
+    }
+
+    derived_rate(db, &base, &quote, None).ok_or(RateError::NoRoute { base, quote })
+}
+
+// Inverse, then via one intermediate currency, ignoring the stored pair `excluded`
+fn derived_rate(db: &Database, base: &str, quote: &str, excluded: Option<u64>) -> Option<Rate> {
+    if let Some((rate, id)) = inverse_leg(db, base, quote, excluded) {
+        return Some(Rate {
+            rate,
+            source: RateSource::Inverse,
+            path: vec![id],
+        });
+    }
+
+    // Try every currency quoted against base, alphabetically so results are stable
+    for via in neighbours(db, base, excluded) {
+        if via == quote {
+            continue;
+        }
+        if let (Some((first, first_id)), Some((second, second_id))) = (
+            leg(db, base, &via, excluded),
+            leg(db, &via, quote, excluded),
+        ) {
+            return Some(Rate {
+                rate: first * second,
+                source: RateSource::Triangulated { via },
+                path: vec![first_id, second_id],
+            });
+        }
+    }
+    None
+}
+
+// The rate of a stored pair as if it were not stored (inverse, else via one intermediate
+// currency): what else it could be priced from. None when nothing else prices it.
+pub fn cross_rate(db: &Database, forex_pair: &ForexPair) -> Option<Rate> {
+    let (base, quote) = forex_pair.pair.split_once('/')?;
+    derived_rate(db, base, quote, Some(forex_pair.id))
+}
+
+// One hop using a stored pair in either direction
+fn leg(db: &Database, from: &str, to: &str, excluded: Option<u64>) -> Option<(f64, u64)> {
+    match find(db, &format!("{}/{}", from, to), excluded) {
+        Some(forex_pair) => Some((forex_pair.price, forex_pair.id)),
+        None => inverse_leg(db, from, to, excluded),
+    }
+}
+
+fn find<'a>(db: &'a Database, pair: &str, excluded: Option<u64>) -> Option<&'a ForexPair> {
+    db.find_by_pair(pair)
+        .filter(|forex_pair| Some(forex_pair.id) != excluded)
+}
+
+// QUOTE/BASE view of a stored BASE/QUOTE record: same id, price 1/price, and bid and ask
+// swapped and inverted. Extra fields describe the stored direction and are left out. None
+// when the price is too close to zero to invert.
+pub fn invert(forex_pair: &ForexPair) -> Option<ForexPair> {
+    let invert_price = |price: f64| (price > MIN_INVERTIBLE_PRICE).then(|| 1.0 / price);
+    let (base, quote) = forex_pair.pair.split_once('/')?;
+    Some(ForexPair {
+        id: forex_pair.id,
+        pair: format!("{}/{}", quote, base),
+        price: invert_price(forex_pair.price)?,
+        bid: forex_pair.ask.and_then(invert_price),
+        ask: forex_pair.bid.and_then(invert_price),
+        created_at: forex_pair.created_at,
+        extra_fields: None,
+    })
+}
+
+fn inverse_leg(db: &Database, from: &str, to: &str, excluded: Option<u64>) -> Option<(f64, u64)> {
+    find(db, &format!("{}/{}", to, from), excluded)
+        .filter(|forex_pair| forex_pair.price > MIN_INVERTIBLE_PRICE)
+        .map(|forex_pair| (1.0 / forex_pair.price, forex_pair.id))
+}
+
+// Currencies that share a stored pair with the given one
+fn neighbours(db: &Database, currency: &str, excluded: Option<u64>) -> BTreeSet<String> {
+    db.iter()
+        .filter(|forex_pair| Some(forex_pair.id) != excluded)
+        .filter_map(|forex_pair: &ForexPair| {
+            let (base, quote) = forex_pair.pair.split_once('/')?;
+            if base == currency {
+                Some(quote.to_string())
+            } else if quote == currency {
+                Some(base.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::forex_pair;
+
+    fn sample_database() -> Database {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.25));
+        db.insert(forex_pair(2, "USD/JPY", 150.0));
+        db.insert(forex_pair(3, "GBP/USD", 1.5));
+        db
+    }
+
+    #[test]
+    fn tests_convert_rate_direct_inverse_and_triangulated() {
+        let db: Database = sample_database();
+
+        let direct: Rate = convert_rate(&db, "EUR", "USD").unwrap();
+        assert_eq!(direct.source, RateSource::Direct);
+        assert_eq!(direct.rate, 1.25);
+
+        let inverse: Rate = convert_rate(&db, "usd", "eur").unwrap();
+        assert_eq!(inverse.source, RateSource::Inverse);
+        assert!((inverse.rate - 0.8).abs() < 1e-12);
+
+        let crossed: Rate = convert_rate(&db, "EUR", "JPY").unwrap();
+        assert_eq!(
+            crossed.source,
+            RateSource::Triangulated {
+                via: "USD".to_string()
+            }
+        );
+        assert!((crossed.rate - 187.5).abs() < 1e-9);
+        assert_eq!(crossed.path, vec![1, 2]);
+        assert!(crossed.is_derived());
+    }
+
+    #[test]
+    fn tests_convert_rate_errors() {
+        let db: Database = sample_database();
+        assert_eq!(
+            convert_rate(&db, "EUR", "CHF"),
+            Err(RateError::NoRoute {
+                base: "EUR".to_string(),
+                quote: "CHF".to_string()
+            })
+        );
+        assert!(matches!(
+            convert_rate(&db, "EURO", "USD"),
+            Err(RateError::InvalidCurrency(_))
+        ));
+    }
+
+    #[test]
+    fn tests_cross_rate_leaves_the_pair_itself_out() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.25));
+        db.insert(forex_pair(2, "USD/JPY", 150.0));
+        db.insert(forex_pair(3, "EUR/JPY", 187.5));
+
+        let eur_jpy: Rate = cross_rate(&db, db.get(&3).unwrap()).unwrap();
+        assert_eq!(eur_jpy.path, vec![1, 2]);
+        assert_eq!(
+            eur_jpy.source,
+            RateSource::Triangulated {
+                via: "USD".to_string()
+            }
+        );
+        assert_eq!(eur_jpy.rate, 187.5);
+        assert!(cross_rate(&sample_database(), &forex_pair(3, "GBP/USD", 1.5)).is_none());
+    }
+}
@@ -0,0 +1,1603 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::{btree_map, BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::hash::Hasher;
+use std::io::{Read, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use twox_hash::XxHash64;
+
+use crate::converter::cross_rate;
+use crate::persistence::Snapshot;
+
+pub const DEFAULT_DATABASE_PATH: &str = "database.json";
+
+// How ids are picked for records created without one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IdStrategy {
+    #[default]
+    Sequential,
+    // Random u64 ids below 2^53 (JSON safe), so routes and the store are unchanged, but they
+    // no longer leak dataset size or clash across merges. These are not UUIDs.
+    Random,
+}
+
+impl std::str::FromStr for IdStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sequential" => Ok(Self::Sequential),
+            "random" => Ok(Self::Random),
+            other => Err(format!("unknown id strategy {:?}", other)),
+        }
+    }
+}
+
+// The fields ForexPair declares; anything else in a body lands in extra_fields
+pub const FOREX_PAIR_FIELDS: &[&str] = &["id", "pair", "price", "bid", "ask", "created_at"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ForexPair {
+    // 0 (or omitted) asks the server to assign one
+    #[serde(default)]
+    pub id: u64,
+    pub pair: String,
+    #[serde(deserialize_with = "deserialize_price")]
+    pub price: f64,
+    // Optional top of book quote, validated against the configured max spread
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bid: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ask: Option<f64>,
+    // Set when the record is first stored, updates keep it. Records from files written before
+    // it existed get their first price point on load, or stay None when they have no history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    // Fields this version does not know about, kept so load/save cycles don't drop them
+    #[serde(flatten)]
+    pub extra_fields: Option<Map<String, Value>>,
+}
+
+// Accept `1.085` as well as `"1.085"` from loosely typed clients
+pub fn deserialize_price<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(f64),
+        String(String),
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(price) => Ok(price),
+        NumberOrString::String(raw) => parse_price(&raw).map_err(de::Error::custom),
+    }
+}
+
+// Numeric strings only: "NaN", "inf" and friends are rejected like any other junk
+pub fn parse_price(raw: &str) -> Result<f64, String> {
+    raw.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|price| price.is_finite())
+        .ok_or_else(|| format!("price {:?} is not a number", raw))
+}
+
+impl ForexPair {
+    // How long ago the record was created. A record of unknown age predates created_at and
+    // counts as older than anything else.
+    pub fn age(&self) -> chrono::Duration {
+        self.created_at
+            .map_or(chrono::Duration::MAX, |created_at| Utc::now() - created_at)
+    }
+
+    // Canonical BASE/QUOTE form: accepts "eur/usd", "EUR-USD", "eur_usd", "EURUSD", ...
+    pub fn normalize_pair(raw: &str) -> Result<String, String> {
+        let cleaned: String = raw.trim().to_ascii_uppercase();
+        let parts: Vec<&str> =
+            if cleaned.len() == 6 && cleaned.chars().all(|c| c.is_ascii_alphabetic()) {
+                vec![&cleaned[..3], &cleaned[3..]]
+            } else {
+                cleaned
+                    .split(|c: char| c == '/' || c == '-' || c == '_' || c.is_whitespace())
+                    .filter(|part| !part.is_empty())
+                    .collect()
+            };
+
+        match parts.as_slice() {
+            [base, quote] if is_currency_code(base) && is_currency_code(quote) => {
+                if base == quote {
+                    Err(format!("pair {:?} uses the same currency twice", raw))
+                } else {
+                    Ok(format!("{}/{}", base, quote))
+                }
+            }
+            _ => Err(format!(
+                "pair {:?} is not of the form BASE/QUOTE with 3-letter currency codes",
+                raw
+            )),
+        }
+    }
+}
+
+// Equality is by id: the same record before and after an update compares equal. Ordering is
+// by price (total_cmp), then id, for sorted collections and `<`/`>`. The two disagree for
+// records with the same id but different prices: == holds while cmp is not Equal, so don't
+// put two versions of one record in the same ordered set.
+impl PartialEq for ForexPair {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for ForexPair {}
+
+impl PartialOrd for ForexPair {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ForexPair {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.price
+            .total_cmp(&other.price)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+// f64 ordered with total_cmp so prices can key an ordered index
+#[derive(Debug, Clone, Copy)]
+pub struct PriceKey(pub f64);
+
+impl PartialEq for PriceKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PriceKey {}
+
+impl PartialOrd for PriceKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PriceKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// One observed price, appended whenever a record is stored with a new price
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PricePoint {
+    pub timestamp: DateTime<Utc>,
+    pub price: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Database {
+    // Ordered by id so id ranges can be read without a full scan
+    forex_pairs: BTreeMap<u64, ForexPair>,
+    #[serde(skip)]
+    database_path: PathBuf,
+    // Pair string -> id, rebuilt whenever records are loaded or swapped in
+    #[serde(skip)]
+    pair_index: HashMap<String, u64>,
+    // (price, id) in price order for neighbour lookups, maintained alongside pair_index
+    #[serde(skip)]
+    price_index: BTreeSet<(PriceKey, u64)>,
+    // Next sequential id, persisted so deleted ids are never handed out again
+    #[serde(default)]
+    next_id: u64,
+    #[serde(skip)]
+    id_strategy: IdStrategy,
+    // Id -> prices oldest first
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    price_history: HashMap<u64, Vec<PricePoint>>,
+    // Id -> when the record was last stored, for If-Unmodified-Since. Missing for records
+    // loaded from files written before this was kept.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    updated_at: HashMap<u64, DateTime<Utc>>,
+    // User id -> favourite pair strings (normalised)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    watchlists: HashMap<String, HashSet<String>>,
+    // Records set aside by repair() because another record already holds their pair
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    quarantine: BTreeMap<u64, ForexPair>,
+}
+
+// The public part of the database in the database.json format, for the unauthenticated
+// export: the records only, never watchlists, history or the quarantine
+#[derive(Serialize, Debug)]
+pub struct DatabaseExport<'a> {
+    forex_pairs: &'a BTreeMap<u64, ForexPair>,
+    next_id: u64,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct MergeSummary {
+    pub inserted: usize,
+    pub updated: usize,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct NormalizeReport {
+    pub changed_count: usize,
+    pub unchanged_count: usize,
+    // Records left alone because their pair is invalid or normalises onto another record
+    pub skipped_ids: Vec<u64>,
+}
+
+// What repair() changed; empty when the database was already consistent
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct RepairReport {
+    // Records whose id disagreed with the key they are stored under
+    pub rekeyed_ids: Vec<u64>,
+    // Duplicate pairs moved to the quarantine, the lowest id keeps the pair
+    pub quarantined_ids: Vec<u64>,
+    // Ids whose price history was dropped because they have no record
+    pub orphaned_history_ids: Vec<u64>,
+    pub index_rebuilt: bool,
+}
+
+// What reindex() corrected; all zero/false/equal when the derived data was sound
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct ReindexReport {
+    // Pair index entries that were missing, pointed at the wrong id or at nothing
+    pub pair_index_fixes: usize,
+    pub price_index_rebuilt: bool,
+    pub next_id_before: u64,
+    pub next_id_after: u64,
+    // Ids whose price history was not in timestamp order
+    pub resorted_history_ids: Vec<u64>,
+}
+
+impl RepairReport {
+    pub fn is_empty(&self) -> bool {
+        *self == RepairReport::default()
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::with_path(DEFAULT_DATABASE_PATH)
+    }
+
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            forex_pairs: BTreeMap::new(),
+            database_path: path.into(),
+            pair_index: HashMap::new(),
+            price_index: BTreeSet::new(),
+            next_id: 1,
+            id_strategy: IdStrategy::default(),
+            price_history: HashMap::new(),
+            updated_at: HashMap::new(),
+            watchlists: HashMap::new(),
+            quarantine: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_id_strategy(&mut self, id_strategy: IdStrategy) {
+        self.id_strategy = id_strategy;
+    }
+
+    // Reserve an id for a new record according to the configured strategy
+    pub fn allocate_id(&mut self) -> u64 {
+        match self.id_strategy {
+            IdStrategy::Sequential => {
+                let id: u64 = self.next_id.max(1);
+                self.next_id = id + 1;
+                id
+            }
+            IdStrategy::Random => loop {
+                let id: u64 = rand::thread_rng().gen_range(1..1 << 53);
+                if !self.forex_pairs.contains_key(&id) {
+                    break id;
+                }
+            },
+        }
+    }
+
+    pub fn insert(&mut self, mut forex_pair: ForexPair) -> Option<ForexPair> {
+        self.keep_created_at(&mut forex_pair);
+        self.next_id = self.next_id.max(forex_pair.id.saturating_add(1));
+        self.pair_index
+            .insert(forex_pair.pair.clone(), forex_pair.id);
+        let (id, price): (u64, f64) = (forex_pair.id, forex_pair.price);
+        let previous: Option<ForexPair> = self.forex_pairs.insert(forex_pair.id, forex_pair);
+        if let Some(previous) = &previous {
+            self.unindex_if_stale(previous);
+            self.price_index
+                .remove(&(PriceKey(previous.price), previous.id));
+        }
+        self.price_index.insert((PriceKey(price), id));
+        self.updated_at.insert(id, Utc::now());
+        if previous.as_ref().map(|previous| previous.price) != Some(price) {
+            self.record_price(id, price, Utc::now());
+        }
+        previous
+    }
+
+    // An update carries over the stored record's created_at whatever the body said, a new
+    // record keeps the one it came with (imports, merges) or is stamped now
+    pub fn keep_created_at(&self, forex_pair: &mut ForexPair) {
+        forex_pair.created_at = match self.forex_pairs.get(&forex_pair.id) {
+            Some(existing) => existing.created_at,
+            None => forex_pair.created_at.or_else(|| Some(Utc::now())),
+        };
+    }
+
+    pub fn record_price(&mut self, id: u64, price: f64, timestamp: DateTime<Utc>) {
+        self.price_history
+            .entry(id)
+            .or_default()
+            .push(PricePoint { timestamp, price });
+    }
+
+    pub fn price_history(&self, id: &u64) -> &[PricePoint] {
+        self.price_history.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    // Drop every history point of a pair, leaving the record and its current price alone.
+    // Returns how many points were removed.
+    pub fn clear_history(&mut self, id: u64) -> usize {
+        self.price_history
+            .remove(&id)
+            .map_or(0, |points| points.len())
+    }
+
+    // When the record was last inserted or updated, None if that is not known
+    pub fn updated_at(&self, id: &u64) -> Option<DateTime<Utc>> {
+        self.updated_at.get(id).copied()
+    }
+
+    pub fn get(&self, id: &u64) -> Option<&ForexPair> {
+        self.forex_pairs.get(id)
+    }
+
+    pub fn get_all(&self) -> Vec<&ForexPair> {
+        self.iter().collect()
+    }
+
+    // Every record in id order, without collecting them first
+    pub fn iter(&self) -> DatabaseIter<'_> {
+        DatabaseIter {
+            inner: self.forex_pairs.values(),
+        }
+    }
+
+    // In-place edits of fields nothing is indexed on (bid, ask, extras). Changing id, pair or
+    // price this way leaves the indexes stale until repair(), use update or rename for those.
+    pub fn iter_mut(&mut self) -> DatabaseIterMut<'_> {
+        DatabaseIterMut {
+            inner: self.forex_pairs.values_mut(),
+        }
+    }
+
+    // Records with from_id <= id <= to_id, in id order
+    pub fn get_range(&self, from_id: u64, to_id: u64) -> Vec<&ForexPair> {
+        if from_id > to_id {
+            return vec![];
+        }
+        self.forex_pairs
+            .range(from_id..=to_id)
+            .map(|(_, forex_pair)| forex_pair)
+            .collect()
+    }
+
+    // Look a record up by its (normalised) pair string
+    pub fn find_by_pair(&self, pair: &str) -> Option<&ForexPair> {
+        self.pair_index
+            .get(pair)
+            .and_then(|id| self.forex_pairs.get(id))
+    }
+
+    pub fn delete(&mut self, id: &u64) -> Option<ForexPair> {
+        let removed: Option<ForexPair> = self.forex_pairs.remove(id);
+        if let Some(removed) = &removed {
+            self.unindex_if_stale(removed);
+            self.price_index
+                .remove(&(PriceKey(removed.price), removed.id));
+            self.price_history.remove(id);
+            self.updated_at.remove(id);
+        }
+        removed
+    }
+
+    pub fn update(&mut self, forex_pair: ForexPair) {
+        self.insert(forex_pair);
+    }
+
+    // Give a record a new pair string, keeping its id and history. The pair index and any
+    // watchlist naming the old pair follow. Returns the record as it was before.
+    pub fn rename(&mut self, id: u64, new_pair: &str) -> Option<ForexPair> {
+        let previous: ForexPair = self.forex_pairs.get(&id)?.clone();
+        let mut renamed: ForexPair = previous.clone();
+        renamed.pair = new_pair.to_string();
+        self.update(renamed);
+        for watchlist in self.watchlists.values_mut() {
+            if watchlist.remove(&previous.pair) {
+                watchlist.insert(new_pair.to_string());
+            }
+        }
+        Some(previous)
+    }
+
+    // Drop an index entry left behind by a record that was removed or renamed
+    fn unindex_if_stale(&mut self, old: &ForexPair) {
+        let still_current: bool = self
+            .forex_pairs
+            .get(&old.id)
+            .is_some_and(|current| current.pair == old.pair);
+        if !still_current && self.pair_index.get(&old.pair) == Some(&old.id) {
+            self.pair_index.remove(&old.pair);
+        }
+    }
+
+    // Other records whose cross rate (converter::cross_rate, the route that prices them without
+    // their own record) passes through id, in id order. Empty when id is unknown.
+    pub fn compute_dependencies(&self, id: u64) -> Vec<u64> {
+        if self.get(&id).is_none() {
+            return vec![];
+        }
+        self.iter()
+            .filter(|forex_pair| forex_pair.id != id)
+            .filter(|forex_pair| {
+                cross_rate(self, forex_pair).is_some_and(|rate| rate.path.contains(&id))
+            })
+            .map(|forex_pair| forex_pair.id)
+            .collect()
+    }
+
+    // Up to n other records with the closest prices, closest first (ties: lower price, then
+    // lower id). Walks outwards from the record in the price index, so it touches n + 2
+    // entries rather than sorting everything. Empty when id is unknown.
+    pub fn nearest_by_price(&self, id: u64, n: usize) -> Vec<&ForexPair> {
+        let Some(origin) = self.forex_pairs.get(&id) else {
+            return vec![];
+        };
+        let key: (PriceKey, u64) = (PriceKey(origin.price), id);
+        let mut below = self
+            .price_index
+            .range(..key)
+            .rev()
+            .map(|(price, id)| (price.0, *id))
+            .peekable();
+        let mut above = self
+            .price_index
+            .range((Bound::Excluded(key), Bound::Unbounded))
+            .map(|(price, id)| (price.0, *id))
+            .peekable();
+
+        let mut nearest: Vec<&ForexPair> = Vec::with_capacity(n.min(self.len()));
+        while nearest.len() < n {
+            let take_below: bool = match (below.peek(), above.peek()) {
+                (Some((low, _)), Some((high, _))) => origin.price - low <= high - origin.price,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let next: Option<(f64, u64)> = if take_below {
+                below.next()
+            } else {
+                above.next()
+            };
+            if let Some(forex_pair) = next.and_then(|(_, id)| self.forex_pairs.get(&id)) {
+                nearest.push(forex_pair);
+            }
+        }
+        nearest
+    }
+
+    fn rebuild_price_index(&mut self) {
+        self.price_index = self
+            .forex_pairs
+            .values()
+            .map(|forex_pair| (PriceKey(forex_pair.price), forex_pair.id))
+            .collect();
+    }
+
+    fn rebuild_pair_index(&mut self) {
+        let mut ids: Vec<&u64> = self.forex_pairs.keys().collect();
+        ids.sort_unstable();
+        let mut index: HashMap<String, u64> = HashMap::new();
+        // Lowest id wins if legacy data holds the same pair twice
+        for id in ids {
+            index
+                .entry(self.forex_pairs[id].pair.clone())
+                .or_insert(*id);
+        }
+        self.pair_index = index;
+    }
+
+    // The n records created longest ago, oldest first (unknown creation time first, ties by
+    // id). Only the n selected records are sorted, not the whole table.
+    pub fn oldest_pairs(&self, n: usize) -> Vec<&ForexPair> {
+        if n == 0 {
+            return vec![];
+        }
+        let mut forex_pairs: Vec<&ForexPair> = self.iter().collect();
+        let key = |forex_pair: &&ForexPair| (forex_pair.created_at, forex_pair.id);
+        if n < forex_pairs.len() {
+            forex_pairs.select_nth_unstable_by_key(n - 1, key);
+            forex_pairs.truncate(n);
+        }
+        forex_pairs.sort_unstable_by_key(key);
+        forex_pairs
+    }
+
+    // xxHash-64 of every record serialised in id order, as 16 hex digits. Two databases
+    // holding the same records give the same checksum; history and watchlists are not part of
+    // it. For clients that mirror the data and want to know whether to fetch it again.
+    pub fn checksum(&self) -> String {
+        let mut hasher: XxHash64 = XxHash64::with_seed(0);
+        for forex_pair in self.iter() {
+            let serialised: Vec<u8> =
+                serde_json::to_vec(forex_pair).expect("ForexPair always serialises to JSON");
+            // Length-prefixed so record boundaries cannot shift between two databases
+            hasher.write_u64(serialised.len() as u64);
+            hasher.write(&serialised);
+        }
+        format!("{:016x}", hasher.finish())
+    }
+
+    pub fn len(&self) -> usize {
+        self.forex_pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forex_pairs.is_empty()
+    }
+
+    pub fn database_path(&self) -> &Path {
+        &self.database_path
+    }
+
+    // Check the records are sound before letting them anywhere near the live data
+    pub fn check_integrity(&self) -> Result<(), Vec<String>> {
+        let mut problems: Vec<String> = vec![];
+        let mut seen_pairs: HashMap<&str, u64> = HashMap::new();
+        for (key, forex_pair) in &self.forex_pairs {
+            if let Some(other) = seen_pairs.insert(&forex_pair.pair, *key) {
+                problems.push(format!(
+                    "pair {} is stored under both id {} and id {}",
+                    forex_pair.pair,
+                    other.min(*key),
+                    other.max(*key)
+                ));
+            }
+            if *key != forex_pair.id {
+                problems.push(format!(
+                    "record stored under id {} has id {}",
+                    key, forex_pair.id
+                ));
+            }
+            if forex_pair.pair.trim().is_empty() {
+                problems.push(format!("record {} has an empty pair", key));
+            }
+            if !forex_pair.price.is_finite() || forex_pair.price <= 0.0 {
+                problems.push(format!(
+                    "record {} has invalid price {}",
+                    key, forex_pair.price
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            problems.sort();
+            Err(problems)
+        }
+    }
+
+    // Fix what check_integrity would report about ids and pairs, plus stale index entries
+    // and orphaned history, so data written by older versions does not fail requests later.
+    // Every fix is logged; records are never dropped, duplicates go to the quarantine.
+    pub fn repair(&mut self) -> RepairReport {
+        let mut report: RepairReport = RepairReport::default();
+
+        // The key is what every route looks records up by, so it wins over the stored id
+        for (key, forex_pair) in self.forex_pairs.iter_mut() {
+            if forex_pair.id != *key {
+                tracing::warn!(
+                    key,
+                    id = forex_pair.id,
+                    "repair: record id did not match its key"
+                );
+                forex_pair.id = *key;
+                report.rekeyed_ids.push(*key);
+            }
+        }
+
+        let mut owners: HashMap<String, u64> = HashMap::new();
+        let mut duplicates: Vec<u64> = vec![];
+        for (id, forex_pair) in &self.forex_pairs {
+            if let Some(owner) = owners.get(&forex_pair.pair) {
+                tracing::warn!(id, owner, pair = %forex_pair.pair, "repair: quarantined duplicate pair");
+                duplicates.push(*id);
+            } else {
+                owners.insert(forex_pair.pair.clone(), *id);
+            }
+        }
+        for id in duplicates {
+            if let Some(forex_pair) = self.forex_pairs.remove(&id) {
+                self.quarantine.insert(id, forex_pair);
+            }
+            report.quarantined_ids.push(id);
+        }
+
+        let mut orphaned: Vec<u64> = self
+            .price_history
+            .keys()
+            .filter(|id| !self.forex_pairs.contains_key(id) && !self.quarantine.contains_key(id))
+            .copied()
+            .collect();
+        orphaned.sort_unstable();
+        for id in &orphaned {
+            tracing::warn!(id, "repair: dropped price history of a missing record");
+            self.price_history.remove(id);
+        }
+        report.orphaned_history_ids = orphaned;
+        let forex_pairs: &BTreeMap<u64, ForexPair> = &self.forex_pairs;
+        let quarantine: &BTreeMap<u64, ForexPair> = &self.quarantine;
+        self.updated_at
+            .retain(|id, _| forex_pairs.contains_key(id) || quarantine.contains_key(id));
+
+        if self.pair_index != owners {
+            tracing::warn!(
+                stale = self.pair_index.len(),
+                rebuilt = owners.len(),
+                "repair: rebuilt the pair index"
+            );
+            self.pair_index = owners;
+            report.index_rebuilt = true;
+        }
+        let price_index: BTreeSet<(PriceKey, u64)> = std::mem::take(&mut self.price_index);
+        self.rebuild_price_index();
+        if self.price_index != price_index {
+            tracing::warn!("repair: rebuilt the price index");
+            report.index_rebuilt = true;
+        }
+        self.sync_next_id();
+        report
+    }
+
+    // Recompute everything derived from the records: the pair and price indexes, the id
+    // counter (raised past the highest id if it fell behind; a higher counter is kept so
+    // deleted ids stay retired) and the timestamp order of every history. Records themselves
+    // are left alone, see repair() for those.
+    pub fn reindex(&mut self) -> ReindexReport {
+        let mut report: ReindexReport = ReindexReport {
+            next_id_before: self.next_id,
+            ..ReindexReport::default()
+        };
+
+        let pair_index: HashMap<String, u64> = std::mem::take(&mut self.pair_index);
+        self.rebuild_pair_index();
+        report.pair_index_fixes = pair_index
+            .iter()
+            .filter(|(pair, id)| self.pair_index.get(*pair) != Some(id))
+            .count()
+            + self
+                .pair_index
+                .keys()
+                .filter(|pair| !pair_index.contains_key(*pair))
+                .count();
+
+        let price_index: BTreeSet<(PriceKey, u64)> = std::mem::take(&mut self.price_index);
+        self.rebuild_price_index();
+        report.price_index_rebuilt = self.price_index != price_index;
+
+        self.sync_next_id();
+        report.next_id_after = self.next_id;
+
+        for (id, points) in self.price_history.iter_mut() {
+            if !points.is_sorted_by_key(|point| point.timestamp) {
+                points.sort_by_key(|point| point.timestamp);
+                report.resorted_history_ids.push(*id);
+            }
+        }
+        report.resorted_history_ids.sort_unstable();
+        report
+    }
+
+    // Renumber every record 1, 2, 3, ... in ascending price order (ties by old id), carrying
+    // price history along; watchlists hold pair strings and need no change. Quarantined
+    // records are renumbered after the live ones so their ids cannot clash. The counter
+    // restarts after the last id. Returns old id -> new id for every record.
+    pub fn recalculate_ids(&mut self) -> BTreeMap<u64, u64> {
+        let mut live: Vec<ForexPair> = std::mem::take(&mut self.forex_pairs)
+            .into_values()
+            .collect();
+        live.sort();
+        let quarantined: Vec<ForexPair> =
+            std::mem::take(&mut self.quarantine).into_values().collect();
+        let mut old_history: HashMap<u64, Vec<PricePoint>> =
+            std::mem::take(&mut self.price_history);
+        let mut old_updated_at: HashMap<u64, DateTime<Utc>> = std::mem::take(&mut self.updated_at);
+
+        let mut mapping: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut next: u64 = 1;
+        for (mut forex_pair, is_live) in
+            live.into_iter().map(|forex_pair| (forex_pair, true)).chain(
+                quarantined
+                    .into_iter()
+                    .map(|forex_pair| (forex_pair, false)),
+            )
+        {
+            let old_id: u64 = forex_pair.id;
+            forex_pair.id = next;
+            if let Some(history) = old_history.remove(&old_id) {
+                self.price_history.insert(next, history);
+            }
+            if let Some(updated_at) = old_updated_at.remove(&old_id) {
+                self.updated_at.insert(next, updated_at);
+            }
+            if is_live {
+                self.forex_pairs.insert(next, forex_pair);
+            } else {
+                self.quarantine.insert(next, forex_pair);
+            }
+            mapping.insert(old_id, next);
+            next += 1;
+        }
+
+        self.next_id = next;
+        self.rebuild_pair_index();
+        self.rebuild_price_index();
+        mapping
+    }
+
+    pub fn export(&self) -> DatabaseExport<'_> {
+        DatabaseExport {
+            forex_pairs: &self.forex_pairs,
+            next_id: self.next_id,
+        }
+    }
+
+    // Records repair() set aside, by id
+    pub fn quarantine(&self) -> &BTreeMap<u64, ForexPair> {
+        &self.quarantine
+    }
+
+    // Upsert every record from other, incoming records (and their history) win on id clashes.
+    // Refused, leaving this database as it was, when that would store a pair twice.
+    pub fn merge(&mut self, other: Database) -> Result<MergeSummary, Vec<String>> {
+        let collisions: Vec<String> = self.pair_collisions(&other);
+        if !collisions.is_empty() {
+            return Err(collisions);
+        }
+        let mut summary: MergeSummary = MergeSummary::default();
+        for (_, forex_pair) in other.forex_pairs {
+            match self.insert(forex_pair) {
+                Some(_) => summary.updated += 1,
+                None => summary.inserted += 1,
+            }
+        }
+        self.price_history.extend(other.price_history);
+        Ok(summary)
+    }
+
+    // Like merge(), but other's records go in exactly as they are: created_at, updated_at and
+    // price history are copied over rather than stamped now, so a dump comes back unchanged
+    pub fn restore(&mut self, other: Database) -> Result<MergeSummary, Vec<String>> {
+        let collisions: Vec<String> = self.pair_collisions(&other);
+        if !collisions.is_empty() {
+            return Err(collisions);
+        }
+        let Database {
+            forex_pairs,
+            mut price_history,
+            mut updated_at,
+            ..
+        } = other;
+        let mut summary: MergeSummary = MergeSummary::default();
+        for (id, forex_pair) in forex_pairs {
+            self.next_id = self.next_id.max(id.saturating_add(1));
+            self.pair_index.insert(forex_pair.pair.clone(), id);
+            let price: f64 = forex_pair.price;
+            match self.forex_pairs.insert(id, forex_pair) {
+                Some(previous) => {
+                    self.unindex_if_stale(&previous);
+                    self.price_index
+                        .remove(&(PriceKey(previous.price), previous.id));
+                    summary.updated += 1;
+                }
+                None => summary.inserted += 1,
+            }
+            self.price_index.insert((PriceKey(price), id));
+            match price_history.remove(&id) {
+                Some(history) => self.price_history.insert(id, history),
+                None => self.price_history.remove(&id),
+            };
+            match updated_at.remove(&id) {
+                Some(timestamp) => self.updated_at.insert(id, timestamp),
+                None => self.updated_at.remove(&id),
+            };
+        }
+        Ok(summary)
+    }
+
+    // Incoming pairs already owned by a different live id that the merge would not overwrite
+    pub fn pair_collisions(&self, other: &Database) -> Vec<String> {
+        other
+            .iter()
+            .filter_map(|forex_pair| {
+                let owner: u64 = *self.pair_index.get(&forex_pair.pair)?;
+                (owner != forex_pair.id && !other.forex_pairs.contains_key(&owner)).then(|| {
+                    format!(
+                        "pair {} (id {}) already exists with id {}",
+                        forex_pair.pair, forex_pair.id, owner
+                    )
+                })
+            })
+            .collect()
+    }
+
+    // Rewrite every pair string into its canonical form, keeping the index in step
+    pub fn normalize_pairs(&mut self) -> NormalizeReport {
+        let mut report: NormalizeReport = NormalizeReport::default();
+        let mut ids: Vec<u64> = self.forex_pairs.keys().copied().collect();
+        ids.sort_unstable();
+
+        for id in ids {
+            let current: &ForexPair = &self.forex_pairs[&id];
+            let normalized: String = match ForexPair::normalize_pair(&current.pair) {
+                Ok(normalized) => normalized,
+                Err(_) => {
+                    report.skipped_ids.push(id);
+                    continue;
+                }
+            };
+            if normalized == current.pair {
+                report.unchanged_count += 1;
+                continue;
+            }
+            if self
+                .pair_index
+                .get(&normalized)
+                .is_some_and(|owner| *owner != id)
+            {
+                report.skipped_ids.push(id);
+                continue;
+            }
+
+            let mut renamed: ForexPair = current.clone();
+            renamed.pair = normalized;
+            self.update(renamed);
+            report.changed_count += 1;
+        }
+        report
+    }
+
+    // True when the pair was not on the user's watchlist yet
+    pub fn add_to_watchlist(&mut self, user_id: &str, pair: &str) -> bool {
+        self.watchlists
+            .entry(user_id.to_string())
+            .or_default()
+            .insert(pair.to_string())
+    }
+
+    pub fn remove_from_watchlist(&mut self, user_id: &str, pair: &str) -> bool {
+        let Some(watchlist) = self.watchlists.get_mut(user_id) else {
+            return false;
+        };
+        let removed: bool = watchlist.remove(pair);
+        if watchlist.is_empty() {
+            self.watchlists.remove(user_id);
+        }
+        removed
+    }
+
+    // The user's watched pairs sorted by pair; names whose record has since gone are skipped
+    pub fn watchlist(&self, user_id: &str) -> Vec<&ForexPair> {
+        let mut forex_pairs: Vec<&ForexPair> = self
+            .watchlists
+            .get(user_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|pair| self.find_by_pair(pair))
+            .collect();
+        forex_pairs.sort_by(|a, b| a.pair.cmp(&b.pair));
+        forex_pairs
+    }
+
+    // Ids present in both databases, in ascending order
+    pub fn colliding_ids(&self, other: &Database) -> Vec<u64> {
+        let mut ids: Vec<u64> = other
+            .forex_pairs
+            .keys()
+            .filter(|id| self.forex_pairs.contains_key(id))
+            .copied()
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    // Swap in other's records while keeping this database's file location
+    pub fn replace(&mut self, other: Database) {
+        self.forex_pairs = other.forex_pairs;
+        self.price_history = other.price_history;
+        self.updated_at = other.updated_at;
+        self.next_id = self.next_id.max(other.next_id);
+        self.rebuild_pair_index();
+        self.rebuild_price_index();
+        self.sync_next_id();
+    }
+
+    // Never let the counter fall behind ids already in use
+    fn sync_next_id(&mut self) {
+        // Quarantined ids count too, so they are never handed to a new record
+        let max_id: u64 = self
+            .forex_pairs
+            .keys()
+            .chain(self.quarantine.keys())
+            .copied()
+            .max()
+            .unwrap_or(0);
+        self.next_id = self.next_id.max(max_id.saturating_add(1));
+    }
+
+    // DATABASE SAVING
+    pub fn save_to_file(&self) -> std::io::Result<()> {
+        let data: String = serde_json::to_string(&self)?;
+        let mut file: fs::File = fs::File::create(&self.database_path)?;
+        file.write_all(data.as_bytes())?;
+        Ok(())
+    }
+
+    // Move the database file to new_path. On error nothing has changed. An existing file at
+    // new_path is never overwritten.
+    pub fn rename_file(&mut self, new_path: &Path) -> std::io::Result<()> {
+        if new_path == self.database_path {
+            return Ok(());
+        }
+        Database::move_file(&serde_json::to_vec(&self)?, &self.database_path, new_path)?;
+        self.database_path = new_path.to_path_buf();
+        Ok(())
+    }
+
+    // The file half of rename_file, for callers that must not hold the database while the disk
+    // is busy. data is written to a temporary file next to new_path and renamed into place
+    // atomically; only once that succeeded does the old file go away.
+    pub fn move_file(data: &[u8], old_path: &Path, new_path: &Path) -> std::io::Result<()> {
+        if new_path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", new_path.display()),
+            ));
+        }
+        let mut temp_name: std::ffi::OsString = new_path.as_os_str().to_owned();
+        temp_name.push(".tmp");
+        let temp_path: PathBuf = PathBuf::from(temp_name);
+        let written: std::io::Result<()> = (|| {
+            let mut file: fs::File = fs::File::create(&temp_path)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+            fs::rename(&temp_path, new_path)
+        })();
+        if let Err(err) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(err);
+        }
+
+        match fs::remove_file(old_path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                tracing::warn!(path = %old_path.display(), error = %err, "could not remove the old database file");
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn set_database_path(&mut self, path: &Path) {
+        self.database_path = path.to_path_buf();
+    }
+
+    pub fn snapshot(&self) -> std::io::Result<Snapshot> {
+        Ok(Snapshot {
+            path: self.database_path.clone(),
+            data: serde_json::to_vec(&self)?,
+            generation: 0,
+        })
+    }
+
+    // Like save_to_file, but retries transient failures with exponential backoff
+    pub async fn save_to_file_with_retry(&self, max_attempts: u32) -> std::io::Result<()> {
+        self.snapshot()?.write_with_retry(max_attempts).await
+    }
+
+    pub fn load_from_file(path: &Path) -> std::io::Result<Self> {
+        let file: fs::File = fs::File::open(path)?;
+        let mut db: Database = Database::from_reader(file)?;
+        db.database_path = path.to_path_buf();
+        Ok(db)
+    }
+
+    // Startup loading: the database at path, or an empty one there (true) when it is missing
+    // or unreadable. Callers that must not drop data check path.exists() when true comes back.
+    pub fn load_or_create(path: &Path) -> (Self, bool) {
+        match Database::load_from_file(path) {
+            Ok(db) => {
+                tracing::info!(path = %path.display(), pairs = db.len(), "loaded database");
+                (db, false)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                tracing::info!(path = %path.display(), "no database file, starting empty");
+                (Database::with_path(path), true)
+            }
+            Err(err) => {
+                tracing::error!(path = %path.display(), error = %err, "could not load database, starting empty");
+                (Database::with_path(path), true)
+            }
+        }
+    }
+
+    // Parse a database in the on-disk format from any reader
+    pub fn from_reader<R: Read>(reader: R) -> std::io::Result<Self> {
+        let mut db: Database = serde_json::from_reader(reader)?;
+        // A record cannot be younger than its first price point
+        for forex_pair in db.forex_pairs.values_mut() {
+            let first: Option<DateTime<Utc>> = db
+                .price_history
+                .get(&forex_pair.id)
+                .and_then(|history| history.first())
+                .map(|point| point.timestamp);
+            forex_pair.created_at = match (forex_pair.created_at, first) {
+                (Some(created_at), Some(first)) => Some(created_at.min(first)),
+                (created_at, first) => created_at.or(first),
+            };
+        }
+        db.rebuild_pair_index();
+        db.rebuild_price_index();
+        db.sync_next_id();
+        Ok(db)
+    }
+}
+
+pub struct DatabaseIter<'a> {
+    inner: btree_map::Values<'a, u64, ForexPair>,
+}
+
+impl<'a> Iterator for DatabaseIter<'a> {
+    type Item = &'a ForexPair;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for DatabaseIter<'_> {}
+
+pub struct DatabaseIterMut<'a> {
+    inner: btree_map::ValuesMut<'a, u64, ForexPair>,
+}
+
+impl<'a> Iterator for DatabaseIterMut<'a> {
+    type Item = &'a mut ForexPair;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for DatabaseIterMut<'_> {}
+
+impl<'a> IntoIterator for &'a Database {
+    type Item = &'a ForexPair;
+    type IntoIter = DatabaseIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::forex_pair;
+
+    #[test]
+    fn tests_check_integrity_reports_bad_records() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.forex_pairs.insert(7, forex_pair(2, "", -1.0));
+
+        let problems: Vec<String> = db.check_integrity().unwrap_err();
+        assert_eq!(problems.len(), 3);
+    }
+
+    #[test]
+    fn tests_merge_counts_inserts_and_updates() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+
+        let mut incoming: Database = Database::new();
+        incoming.insert(forex_pair(1, "EUR/USD", 1.09));
+        incoming.insert(forex_pair(2, "GBP/USD", 1.27));
+
+        let summary: MergeSummary = db.merge(incoming).unwrap();
+        assert_eq!(
+            summary,
+            MergeSummary {
+                inserted: 1,
+                updated: 1
+            }
+        );
+        assert_eq!(db.get(&1).unwrap().price, 1.09);
+    }
+
+    #[test]
+    fn tests_restore_keeps_timestamps_and_history() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+
+        let stored: DateTime<Utc> = "2024-03-01T12:00:00Z".parse().unwrap();
+        let mut incoming: Database = Database::new();
+        incoming
+            .forex_pairs
+            .insert(1, forex_pair(1, "EUR/USD", 1.09));
+        incoming
+            .forex_pairs
+            .insert(2, forex_pair(2, "GBP/USD", 1.27));
+        incoming.record_price(1, 1.07, stored);
+        incoming.record_price(1, 1.09, stored);
+        incoming.updated_at.insert(1, stored);
+
+        let summary: MergeSummary = db.restore(incoming).unwrap();
+        assert_eq!(
+            summary,
+            MergeSummary {
+                inserted: 1,
+                updated: 1
+            }
+        );
+        assert_eq!(db.updated_at(&1), Some(stored));
+        assert_eq!(db.updated_at(&2), None);
+        assert_eq!(db.price_history(&1).len(), 2);
+        assert!(db.price_history(&2).is_empty());
+        assert_eq!(db.get(&2).unwrap().created_at, None);
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().price, 1.09);
+        assert_eq!(db.allocate_id(), 3);
+        assert!(db.check_integrity().is_ok());
+
+        let mut incoming: Database = Database::new();
+        incoming.insert(forex_pair(7, "GBP/USD", 1.3));
+        assert!(db.restore(incoming).is_err());
+        assert!(db.get(&7).is_none());
+    }
+
+    #[test]
+    fn tests_merge_refuses_duplicate_pairs() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+
+        let mut incoming: Database = Database::new();
+        incoming.insert(forex_pair(5, "EUR/USD", 1.09));
+        incoming.insert(forex_pair(6, "AUD/USD", 0.66));
+        let problems: Vec<String> = db.merge(incoming).unwrap_err();
+        assert_eq!(
+            problems,
+            vec!["pair EUR/USD (id 5) already exists with id 1"]
+        );
+        assert_eq!(db.len(), 2);
+        assert!(db.check_integrity().is_ok());
+
+        // Fine when the merge also moves the live owner onto another pair
+        let mut incoming: Database = Database::new();
+        incoming.insert(forex_pair(5, "EUR/USD", 1.09));
+        incoming.insert(forex_pair(1, "EUR/CHF", 0.95));
+        db.merge(incoming).unwrap();
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().id, 5);
+        assert!(db.check_integrity().is_ok());
+    }
+
+    #[test]
+    fn tests_normalize_pair() {
+        for raw in [
+            "EUR/USD",
+            "eur/usd",
+            " Eur-Usd ",
+            "eur_usd",
+            "EURUSD",
+            "eur usd",
+        ] {
+            assert_eq!(ForexPair::normalize_pair(raw).unwrap(), "EUR/USD");
+        }
+        for raw in ["", "EUR", "EURO/USD", "EUR/USD/JPY", "EU1/USD", "USD/USD"] {
+            assert!(ForexPair::normalize_pair(raw).is_err(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn tests_pair_index_follows_updates_and_deletes() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().id, 1);
+
+        db.update(forex_pair(1, "EUR/GBP", 0.85));
+        assert!(db.find_by_pair("EUR/USD").is_none());
+        assert_eq!(db.find_by_pair("EUR/GBP").unwrap().id, 1);
+
+        db.delete(&1);
+        assert!(db.find_by_pair("EUR/GBP").is_none());
+    }
+
+    #[test]
+    fn tests_normalize_pairs_skips_collisions() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "eurusd", 1.08));
+        db.insert(forex_pair(3, "not a pair", 1.0));
+        db.insert(forex_pair(4, "gbp-usd", 1.27));
+
+        let report: NormalizeReport = db.normalize_pairs();
+        assert_eq!(report.changed_count, 1);
+        assert_eq!(report.unchanged_count, 1);
+        assert_eq!(report.skipped_ids, vec![2, 3]);
+        assert_eq!(db.find_by_pair("GBP/USD").unwrap().id, 4);
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().id, 1);
+    }
+
+    #[test]
+    fn tests_sequential_ids_are_not_reused() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(7, "EUR/USD", 1.08));
+        assert_eq!(db.allocate_id(), 8);
+        db.delete(&7);
+        assert_eq!(db.allocate_id(), 9);
+    }
+
+    #[test]
+    fn tests_random_ids_are_unique_and_not_sequential() {
+        let mut db: Database = Database::new();
+        db.set_id_strategy(IdStrategy::Random);
+        let mut ids: Vec<u64> = (0..100).map(|_| db.allocate_id()).collect();
+        assert!(ids.iter().all(|id| *id > 0 && *id < (1 << 53)));
+        assert!(ids.windows(2).any(|pair| pair[1] != pair[0] + 1));
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), 100);
+    }
+
+    #[test]
+    fn tests_unknown_fields_survive_load_and_save() {
+        let path: PathBuf = crate::test_support::temp_dir("extra_fields").join("database.json");
+        let legacy: Value = serde_json::json!({
+            "forex_pairs": {
+                "1": { "id": 1, "pair": "EUR/USD", "price": 1.08, "venue": "LMAX", "tags": ["major"] }
+            }
+        });
+        fs::write(&path, legacy.to_string()).unwrap();
+
+        let db: Database = Database::load_from_file(&path).unwrap();
+        let extra: &Map<String, Value> = db.get(&1).unwrap().extra_fields.as_ref().unwrap();
+        assert_eq!(extra["venue"], "LMAX");
+        db.save_to_file().unwrap();
+
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["forex_pairs"], legacy["forex_pairs"]);
+    }
+
+    #[test]
+    fn tests_price_accepts_numbers_and_numeric_strings() {
+        let parse = |price: Value| {
+            serde_json::from_value::<ForexPair>(
+                serde_json::json!({ "pair": "EUR/USD", "price": price }),
+            )
+        };
+        assert_eq!(parse(serde_json::json!(1.085)).unwrap().price, 1.085);
+        assert_eq!(parse(serde_json::json!(" 1.085")).unwrap().price, 1.085);
+        assert_eq!(parse(serde_json::json!(2)).unwrap().price, 2.0);
+
+        for invalid in [
+            serde_json::json!("1.08x"),
+            serde_json::json!("NaN"),
+            serde_json::json!(""),
+            serde_json::json!(true),
+        ] {
+            assert!(parse(invalid.clone()).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn tests_repair_rebuilds_a_stale_pair_index() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        assert!(db.repair().is_empty());
+
+        db.pair_index.insert("EUR/USD".to_string(), 2);
+        db.pair_index.insert("USD/JPY".to_string(), 5);
+        let report: RepairReport = db.repair();
+        assert!(report.index_rebuilt);
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().id, 1);
+        assert!(db.find_by_pair("USD/JPY").is_none());
+        assert!(db.repair().is_empty());
+    }
+
+    #[test]
+    fn tests_forex_pair_equality_by_id_ordering_by_price() {
+        let cheap: ForexPair = forex_pair(2, "USD/JPY", 0.5);
+        let dear: ForexPair = forex_pair(1, "EUR/USD", 1.08);
+        let dear_again: ForexPair = forex_pair(3, "GBP/USD", 1.08);
+
+        assert!(cheap < dear);
+        assert!(dear > cheap);
+        assert!(cheap <= dear && dear <= dear.clone());
+        assert!(dear >= cheap && dear >= dear.clone());
+        assert!(dear != cheap);
+        // Same price: the lower id sorts first
+        assert!(dear < dear_again);
+        assert_eq!(dear.cmp(&dear.clone()), Ordering::Equal);
+
+        // Equality looks at the id only
+        let repriced: ForexPair = forex_pair(1, "EUR/USD", 1.5);
+        assert!(dear == repriced);
+        assert!(dear < repriced);
+
+        let sorted: Vec<u64> = BTreeSet::from([dear_again.clone(), dear, cheap])
+            .iter()
+            .map(|forex_pair| forex_pair.id)
+            .collect();
+        assert_eq!(sorted, vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn tests_iter_yields_records_in_id_order() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(3, "USD/JPY", 151.2));
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+
+        let ids: Vec<u64> = db.iter().map(|forex_pair| forex_pair.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(db.iter().len(), 3);
+
+        for forex_pair in db.iter_mut() {
+            forex_pair.price *= 2.0;
+        }
+        assert_eq!(db.get(&1).unwrap().price, 2.16);
+        assert_eq!((&db).into_iter().count(), db.get_all().len());
+    }
+
+    #[test]
+    fn tests_recalculate_ids_orders_by_price_and_keeps_history() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(10, "USD/JPY", 151.2));
+        db.insert(forex_pair(4, "EUR/USD", 1.08));
+        db.insert(forex_pair(25, "AUD/USD", 0.66));
+        db.update(forex_pair(4, "EUR/USD", 1.09));
+
+        let mapping: BTreeMap<u64, u64> = db.recalculate_ids();
+        assert_eq!(mapping, BTreeMap::from([(25, 1), (4, 2), (10, 3)]));
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().id, 2);
+        assert_eq!(db.price_history(&2).len(), 2);
+        assert!(db.check_integrity().is_ok());
+        assert_eq!(db.allocate_id(), 4);
+    }
+
+    #[test]
+    fn tests_oldest_pairs_in_creation_order() {
+        let now: DateTime<Utc> = Utc::now();
+        let mut db: Database = Database::new();
+        for (id, days_ago) in [(1, 3), (2, 10), (3, 1), (4, 30), (5, 10), (6, 7)] {
+            let mut forex_pair: ForexPair = forex_pair(id, &format!("P{:02}/USD", id), 1.0);
+            forex_pair.created_at = Some(now - chrono::Duration::days(days_ago));
+            db.insert(forex_pair);
+        }
+
+        let ids = |oldest: Vec<&ForexPair>| -> Vec<u64> {
+            oldest.iter().map(|forex_pair| forex_pair.id).collect()
+        };
+        assert_eq!(ids(db.oldest_pairs(3)), vec![4, 2, 5]);
+        assert_eq!(ids(db.oldest_pairs(10)), vec![4, 2, 5, 6, 1, 3]);
+        assert!(db.oldest_pairs(0).is_empty());
+        assert!(db.get(&4).unwrap().age() >= chrono::Duration::days(30));
+
+        // Updates keep the creation time; new records are stamped now
+        db.update(forex_pair(4, "P04/USD", 2.0));
+        db.insert(forex_pair(7, "P07/USD", 1.0));
+        assert_eq!(ids(db.oldest_pairs(1)), vec![4]);
+        assert!(db.get(&7).unwrap().age() < chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn tests_checksum_follows_content() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        let original: String = db.checksum();
+        assert_eq!(original.len(), 16);
+        assert_eq!(db.checksum(), original);
+
+        db.insert(forex_pair(3, "USD/JPY", 151.2));
+        let inserted: String = db.checksum();
+        assert_ne!(inserted, original);
+
+        db.delete(&3);
+        assert_eq!(db.checksum(), original);
+
+        let removed: ForexPair = db.delete(&1).unwrap();
+        let deleted: String = db.checksum();
+        assert_ne!(deleted, original);
+
+        // Back to the original records, back to the original checksum
+        db.insert(removed);
+        assert_eq!(db.checksum(), original);
+    }
+
+    #[test]
+    fn tests_compute_dependencies_follows_cross_rate_paths() {
+        // EUR/JPY can be priced through EUR/USD and USD/JPY, and each of those through
+        // EUR/JPY and the other one
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.25));
+        db.insert(forex_pair(2, "USD/JPY", 150.0));
+        db.insert(forex_pair(3, "EUR/JPY", 187.5));
+        assert_eq!(db.compute_dependencies(2), vec![1, 3]);
+        assert_eq!(db.compute_dependencies(3), vec![1, 2]);
+        assert!(db.compute_dependencies(9).is_empty());
+
+        // Nothing else to price GBP/USD from
+        db.insert(forex_pair(4, "GBP/USD", 1.5));
+        assert!(db.compute_dependencies(4).is_empty());
+        assert!(!db.compute_dependencies(1).contains(&4));
+    }
+
+    #[test]
+    fn tests_nearest_by_price() {
+        let mut db: Database = Database::new();
+        let prices: [f64; 10] = [1.0, 2.0, 4.0, 4.5, 5.2, 7.0, 10.0, 10.1, 15.0, 30.0];
+        for (index, price) in prices.iter().enumerate() {
+            let id: u64 = index as u64 + 1;
+            db.insert(forex_pair(id, &format!("P{:02}/USD", id), *price));
+        }
+
+        let ids = |nearest: Vec<&ForexPair>| -> Vec<u64> {
+            nearest.iter().map(|forex_pair| forex_pair.id).collect()
+        };
+        // 5.2: 4.5 is 0.7 away, 4.0 is 1.2, 7.0 is 1.8
+        assert_eq!(ids(db.nearest_by_price(5, 2)), vec![4, 3]);
+        assert_eq!(ids(db.nearest_by_price(5, 3)), vec![4, 3, 6]);
+        assert_eq!(ids(db.nearest_by_price(7, 2)), vec![8, 6]);
+        assert_eq!(ids(db.nearest_by_price(1, 2)), vec![2, 3]);
+        assert_eq!(ids(db.nearest_by_price(10, 2)), vec![9, 8]);
+        assert_eq!(db.nearest_by_price(1, 50).len(), 9);
+        assert!(db.nearest_by_price(99, 2).is_empty());
+
+        // The index follows updates and deletes
+        db.update(forex_pair(9, "P09/USD", 5.0));
+        db.delete(&4);
+        assert_eq!(ids(db.nearest_by_price(5, 2)), vec![9, 3]);
+    }
+
+    #[test]
+    fn tests_reindex_repairs_derived_data() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        db.insert(forex_pair(3, "USD/JPY", 151.2));
+        let clean: ReindexReport = db.reindex();
+        assert_eq!(clean.pair_index_fixes, 0);
+        assert!(!clean.price_index_rebuilt);
+        assert_eq!(clean.next_id_before, clean.next_id_after);
+
+        db.pair_index.insert("EUR/USD".to_string(), 3);
+        db.pair_index.insert("AUD/USD".to_string(), 9);
+        db.pair_index.remove("USD/JPY");
+        db.price_index.clear();
+        db.next_id = 2;
+        let now: DateTime<Utc> = Utc::now();
+        db.record_price(2, 1.26, now - chrono::Duration::hours(1));
+
+        let report: ReindexReport = db.reindex();
+        assert_eq!(report.pair_index_fixes, 3);
+        assert!(report.price_index_rebuilt);
+        assert_eq!((report.next_id_before, report.next_id_after), (2, 4));
+        assert_eq!(report.resorted_history_ids, vec![2]);
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().id, 1);
+        assert_eq!(db.find_by_pair("USD/JPY").unwrap().id, 3);
+        assert!(db.find_by_pair("AUD/USD").is_none());
+        assert_eq!(db.price_history(&2)[0].price, 1.26);
+        assert_eq!(db.nearest_by_price(1, 1)[0].id, 2);
+    }
+
+    #[test]
+    fn tests_rename_file_moves_the_data() {
+        let dir: PathBuf = crate::test_support::temp_dir("rename_file");
+        let old_path: PathBuf = dir.join("database.json");
+        let new_path: PathBuf = dir.join("moved").join("forex.json");
+        fs::create_dir_all(new_path.parent().unwrap()).unwrap();
+        let mut db: Database = Database::with_path(&old_path);
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.save_to_file().unwrap();
+
+        db.rename_file(&new_path).unwrap();
+        assert!(!old_path.exists());
+        assert_eq!(db.database_path(), new_path);
+        assert_eq!(
+            Database::load_from_file(&new_path)
+                .unwrap()
+                .get(&1)
+                .unwrap()
+                .price,
+            1.08
+        );
+
+        // Never over an existing file, and a failed move changes nothing
+        fs::write(&old_path, "{}").unwrap();
+        assert!(db.rename_file(&old_path).is_err());
+        assert!(db
+            .rename_file(&dir.join("missing").join("db.json"))
+            .is_err());
+        assert_eq!(db.database_path(), new_path);
+        assert!(new_path.exists());
+    }
+
+    #[test]
+    fn tests_load_or_create() {
+        let dir: PathBuf = crate::test_support::temp_dir("load_or_create");
+        let path: PathBuf = dir.join("database.json");
+
+        let (db, created): (Database, bool) = Database::load_or_create(&path);
+        assert!(created);
+        assert!(db.is_empty());
+        assert_eq!(db.database_path(), path);
+        assert_eq!(db.next_id, Database::new().next_id);
+
+        let mut db: Database = db;
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.save_to_file().unwrap();
+        let (db, created): (Database, bool) = Database::load_or_create(&path);
+        assert!(!created);
+        assert_eq!(db.get(&1).unwrap().pair, "EUR/USD");
+    }
+
+    #[tokio::test]
+    async fn tests_save_to_file_with_retry_reports_final_error() {
+        let dir: PathBuf = crate::test_support::temp_dir("save_retry");
+        let mut db: Database = Database::with_path(dir.join("database.json"));
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.save_to_file_with_retry(3).await.unwrap();
+        assert_eq!(
+            Database::load_from_file(&dir.join("database.json"))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let missing: Database = Database::with_path(dir.join("missing").join("database.json"));
+        let err: std::io::Error = missing.save_to_file_with_retry(2).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+}
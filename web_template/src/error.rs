@@ -0,0 +1,121 @@
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+use serde_json::json;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized,
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    // 412, a conditional request header did not hold
+    PreconditionFailed(String),
+    UnsupportedMediaType(String),
+    ServiceUnavailable(String),
+    // 503 with a Retry-After header, in seconds
+    RetryLater { message: String, retry_after: u64 },
+    BadGateway(String),
+    Internal(String),
+}
+
+impl AppError {
+    // Short machine readable code sent alongside the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest(_) => "bad_request",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::NotFound(_) => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::PreconditionFailed(_) => "precondition_failed",
+            Self::UnsupportedMediaType(_) => "unsupported_media_type",
+            Self::ServiceUnavailable(_) | Self::RetryLater { .. } => "service_unavailable",
+            Self::BadGateway(_) => "bad_gateway",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "missing or invalid admin token"),
+            Self::BadRequest(msg)
+            | Self::Forbidden(msg)
+            | Self::NotFound(msg)
+            | Self::Conflict(msg)
+            | Self::PreconditionFailed(msg)
+            | Self::UnsupportedMediaType(msg)
+            | Self::ServiceUnavailable(msg)
+            | Self::RetryLater { message: msg, .. }
+            | Self::BadGateway(msg)
+            | Self::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            Self::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::ServiceUnavailable(_) | Self::RetryLater { .. } => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Self::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut builder = HttpResponse::build(self.status_code());
+        if let Self::RetryLater { retry_after, .. } = self {
+            builder.insert_header((header::RETRY_AFTER, *retry_after));
+        }
+        builder.json(json!({
+            "error": self.code(),
+            "message": self.to_string(),
+        }))
+    }
+}
+
+// Minimal page for browsers, see middleware::html_errors
+pub fn error_page(status: StatusCode, code: &str, message: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{status}</title></head>\n\
+         <body>\n<h1>{status}</h1>\n<p>{message}</p>\n<p><code>{code}</code></p>\n</body>\n</html>\n",
+        status = escape_html(&status.to_string()),
+        message = escape_html(message),
+        code = escape_html(code),
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Internal(format!("failed to persist database: {}", err))
+    }
+}
@@ -0,0 +1,285 @@
+use crate::database::ForexPair;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+
+// Actor on events the server raises itself rather than on behalf of a request
+pub const SYSTEM_ACTOR: &str = "system";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventType {
+    Created,
+    Updated,
+    Deleted,
+    Patched,
+    IdsRecalculated,
+    PriceAlert,
+    MarketOpen,
+    MarketClose,
+    Startup,
+    Shutdown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ForexPairEventData {
+    Created {
+        forex_pair: ForexPair,
+    },
+    Updated {
+        before: ForexPair,
+        after: ForexPair,
+    },
+    Deleted {
+        forex_pair: ForexPair,
+    },
+    // Only the fields a PATCH changed, rather than the whole record twice
+    Patched {
+        changes: Vec<FieldDiff>,
+    },
+    // Every record renumbered at once; old id -> new id
+    IdsRecalculated {
+        mapping: BTreeMap<u64, u64>,
+    },
+    PriceAlert {
+        pair: String,
+        price: f64,
+        threshold: f64,
+    },
+    // A trading session opening or closing, e.g. "London"
+    MarketOpen {
+        market: String,
+    },
+    MarketClose {
+        market: String,
+    },
+    // Server lifecycle, for the webhook
+    Startup {
+        version: String,
+        pair_count: usize,
+        bind_address: String,
+    },
+    Shutdown {
+        uptime_secs: u64,
+        pair_count: usize,
+    },
+}
+
+impl ForexPairEventData {
+    pub fn event_type(&self) -> EventType {
+        match self {
+            Self::Created { .. } => EventType::Created,
+            Self::Updated { .. } => EventType::Updated,
+            Self::Deleted { .. } => EventType::Deleted,
+            Self::Patched { .. } => EventType::Patched,
+            Self::IdsRecalculated { .. } => EventType::IdsRecalculated,
+            Self::PriceAlert { .. } => EventType::PriceAlert,
+            Self::MarketOpen { .. } => EventType::MarketOpen,
+            Self::MarketClose { .. } => EventType::MarketClose,
+            Self::Startup { .. } => EventType::Startup,
+            Self::Shutdown { .. } => EventType::Shutdown,
+        }
+    }
+}
+
+// One top-level field of a pair that differs between two versions of it. A field missing
+// on one side (e.g. a bid being cleared) shows up as null there.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub field: String,
+    pub old_value: Value,
+    pub new_value: Value,
+}
+
+impl FieldDiff {
+    // Compare the serialised JSON objects field by field, in field name order
+    pub fn between(before: &ForexPair, after: &ForexPair) -> Vec<FieldDiff> {
+        let before: Map<String, Value> = as_object(before);
+        let after: Map<String, Value> = as_object(after);
+        let fields: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        fields
+            .into_iter()
+            .filter_map(|field| {
+                let old_value: Value = before.get(field).cloned().unwrap_or(Value::Null);
+                let new_value: Value = after.get(field).cloned().unwrap_or(Value::Null);
+                (old_value != new_value).then(|| FieldDiff {
+                    field: field.clone(),
+                    old_value,
+                    new_value,
+                })
+            })
+            .collect()
+    }
+}
+
+fn as_object(forex_pair: &ForexPair) -> Map<String, Value> {
+    match Value::from(forex_pair.clone()) {
+        Value::Object(fields) => fields,
+        _ => Map::new(),
+    }
+}
+
+// The one event shape shared by every subsystem that reports on pair changes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ForexPairEvent {
+    pub id: Uuid,
+    pub event_type: EventType,
+    pub timestamp: DateTime<Utc>,
+    pub actor: String,
+    pub pair_id: u64,
+    pub data: ForexPairEventData,
+}
+
+impl ForexPairEvent {
+    // event_type is always derived from data so the two can never disagree
+    pub fn new(actor: &str, pair_id: u64, data: ForexPairEventData) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            event_type: data.event_type(),
+            timestamp: Utc::now(),
+            actor: actor.to_string(),
+            pair_id,
+            data,
+        }
+    }
+
+    pub fn created(actor: &str, forex_pair: &ForexPair) -> Self {
+        Self::new(
+            actor,
+            forex_pair.id,
+            ForexPairEventData::Created {
+                forex_pair: forex_pair.clone(),
+            },
+        )
+    }
+
+    pub fn updated(actor: &str, before: &ForexPair, after: &ForexPair) -> Self {
+        Self::new(
+            actor,
+            after.id,
+            ForexPairEventData::Updated {
+                before: before.clone(),
+                after: after.clone(),
+            },
+        )
+    }
+
+    pub fn patched(actor: &str, pair_id: u64, changes: Vec<FieldDiff>) -> Self {
+        Self::new(actor, pair_id, ForexPairEventData::Patched { changes })
+    }
+
+    // A bulk change, not about one pair: pair_id is 0
+    pub fn ids_recalculated(actor: &str, mapping: BTreeMap<u64, u64>) -> Self {
+        Self::new(actor, 0, ForexPairEventData::IdsRecalculated { mapping })
+    }
+
+    pub fn price_alert(actor: &str, forex_pair: &ForexPair, threshold: f64) -> Self {
+        Self::new(
+            actor,
+            forex_pair.id,
+            ForexPairEventData::PriceAlert {
+                pair: forex_pair.pair.clone(),
+                price: forex_pair.price,
+                threshold,
+            },
+        )
+    }
+
+    // Market sessions concern every pair: pair_id is 0
+    pub fn market_open(actor: &str, market: impl Into<String>) -> Self {
+        Self::new(
+            actor,
+            0,
+            ForexPairEventData::MarketOpen {
+                market: market.into(),
+            },
+        )
+    }
+
+    pub fn market_close(actor: &str, market: impl Into<String>) -> Self {
+        Self::new(
+            actor,
+            0,
+            ForexPairEventData::MarketClose {
+                market: market.into(),
+            },
+        )
+    }
+
+    // Not about one pair either: pair_id is 0
+    pub fn startup(pair_count: usize, bind_address: impl Into<String>) -> Self {
+        Self::new(
+            SYSTEM_ACTOR,
+            0,
+            ForexPairEventData::Startup {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                pair_count,
+                bind_address: bind_address.into(),
+            },
+        )
+    }
+
+    pub fn shutdown(uptime_secs: u64, pair_count: usize) -> Self {
+        Self::new(
+            SYSTEM_ACTOR,
+            0,
+            ForexPairEventData::Shutdown {
+                uptime_secs,
+                pair_count,
+            },
+        )
+    }
+
+    pub fn deleted(actor: &str, forex_pair: &ForexPair) -> Self {
+        Self::new(
+            actor,
+            forex_pair.id,
+            ForexPairEventData::Deleted {
+                forex_pair: forex_pair.clone(),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::forex_pair;
+
+    #[test]
+    fn tests_event_json_shape() {
+        let before: ForexPair = forex_pair(1, "EUR/USD", 1.08);
+        let after: ForexPair = forex_pair(1, "EUR/USD", 1.09);
+        let event: ForexPairEvent = ForexPairEvent::updated("admin", &before, &after);
+
+        let json: Value = serde_json::to_value(&event).unwrap();
+        let mut keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            ["actor", "data", "event_type", "id", "pair_id", "timestamp"]
+        );
+        assert_eq!(json["event_type"], "updated");
+        assert_eq!(json["data"]["updated"]["after"]["price"], 1.09);
+
+        let parsed: ForexPairEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.event_type, parsed.data.event_type());
+    }
+
+    #[test]
+    fn tests_field_diff_lists_changed_fields_only() {
+        let mut before: ForexPair = forex_pair(1, "EUR/USD", 1.08);
+        before.bid = Some(1.0799);
+        let after: ForexPair = forex_pair(1, "EUR/USD", 1.09);
+
+        let changes: Vec<FieldDiff> = FieldDiff::between(&before, &after);
+        let fields: Vec<&str> = changes.iter().map(|diff| diff.field.as_str()).collect();
+        assert_eq!(fields, ["bid", "price"]);
+        assert_eq!(changes[0].new_value, Value::Null);
+        assert_eq!(changes[1].old_value, 1.08);
+        assert!(FieldDiff::between(&after, &after).is_empty());
+    }
+}
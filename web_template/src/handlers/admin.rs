@@ -0,0 +1,458 @@
+use crate::auth::Admin;
+use crate::breaker::BreakerState;
+use crate::database::{Database, ReindexReport};
+use crate::error::AppError;
+use crate::maintenance::MaintenanceStatus;
+use crate::paths::{data_dir, resolve_in_dir};
+use crate::persistence::Snapshot;
+use crate::state::{AppState, WritePermit};
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::TryLockError;
+
+// Metadata only: never include pair data here, this route bypasses normal access patterns
+#[derive(Serialize, Debug)]
+pub struct DebugState {
+    pub pair_count: Option<usize>,
+    pub lock_poisoned: bool,
+    pub lock_contended: bool,
+    // Changes not yet saved to the database file
+    pub dirty: bool,
+    // State of the price provider's circuit breaker
+    pub circuit_breaker_state: BreakerState,
+}
+
+pub async fn debug_state(_admin: Admin, app_state: web::Data<AppState>) -> impl Responder {
+    let dirty: bool = app_state.is_dirty();
+    let circuit_breaker_state: BreakerState = app_state.provider_breaker.status().state;
+    // Never block on the data lock, a stuck lock is exactly what this route should reveal
+    let state: DebugState = match app_state.db.try_lock() {
+        Ok(db) => DebugState {
+            pair_count: Some(db.len()),
+            lock_poisoned: false,
+            lock_contended: false,
+            dirty,
+            circuit_breaker_state,
+        },
+        Err(TryLockError::Poisoned(poisoned)) => DebugState {
+            pair_count: Some(poisoned.into_inner().len()),
+            lock_poisoned: true,
+            lock_contended: false,
+            dirty,
+            circuit_breaker_state,
+        },
+        Err(TryLockError::WouldBlock) => DebugState {
+            pair_count: None,
+            lock_poisoned: app_state.db.is_poisoned(),
+            lock_contended: true,
+            dirty,
+            circuit_breaker_state,
+        },
+    };
+
+    HttpResponse::Ok().json(state)
+}
+
+// Recent create/update/delete events, oldest first
+pub async fn audit_log(_admin: Admin, app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(app_state.audit.entries())
+}
+
+// Current read-only state, active window and the next scheduled one
+pub async fn maintenance_status(_admin: Admin, app_state: web::Data<AppState>) -> impl Responder {
+    let status: MaintenanceStatus = app_state.maintenance.status();
+    HttpResponse::Ok().json(status)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReadOnlyToggle {
+    pub read_only: bool,
+}
+
+// Manual read-only switch; scheduled windows still apply while it is off
+pub async fn set_maintenance(
+    _admin: Admin,
+    app_state: web::Data<AppState>,
+    toggle: web::Json<ReadOnlyToggle>,
+) -> impl Responder {
+    app_state.maintenance.set_read_only(toggle.read_only);
+    let status: MaintenanceStatus = app_state.maintenance.status();
+    HttpResponse::Ok().json(status)
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReloadReport {
+    // No file was there, the live database is now empty
+    pub created: bool,
+    pub pair_count: usize,
+}
+
+// Re-read the database file, e.g. after editing it by hand
+pub async fn reload(
+    _admin: Admin,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let created: bool = app_state.reload().await?;
+    let pair_count: usize = app_state.db.lock().unwrap().len();
+    Ok(HttpResponse::Ok().json(ReloadReport {
+        created,
+        pair_count,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RelocateRequest {
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RelocateReport {
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+    pub pair_count: usize,
+}
+
+// Move the database file, e.g. to another name, without a restart. The path is resolved
+// inside the data directory; 409 when something is already there.
+pub async fn relocate_database(
+    _admin: Admin,
+    app_state: web::Data<AppState>,
+    relocate: web::Json<RelocateRequest>,
+) -> Result<HttpResponse, AppError> {
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let new_path: PathBuf =
+        resolve_in_dir(data_dir(&app_state.config.database_path), &relocate.path)?;
+    let old_path: PathBuf = app_state.relocate_database(&new_path).await?;
+    let pair_count: usize = app_state.db.lock().unwrap().len();
+    tracing::info!(old = %old_path.display(), new = %new_path.display(), "relocated database");
+    Ok(HttpResponse::Ok().json(RelocateReport {
+        old_path,
+        new_path,
+        pair_count,
+    }))
+}
+
+// Rebuild the indexes, id counter and history order without a restart, e.g. after the
+// file was edited by hand. Saved only when something that is persisted changed.
+pub async fn reindex(
+    _admin: Admin,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (report, snapshot): (ReindexReport, Option<Snapshot>) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let report: ReindexReport = db.reindex();
+        let snapshot: Option<Snapshot> = if report.next_id_before != report.next_id_after
+            || !report.resorted_history_ids.is_empty()
+        {
+            Some(app_state.snapshot(&db)?)
+        } else {
+            None
+        };
+        (report, snapshot)
+    };
+    if let Some(snapshot) = snapshot {
+        app_state.persist(snapshot).await?;
+    }
+    tracing::info!(?report, "reindexed database");
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::maintenance::MaintenanceWindow;
+    use crate::test_support::{
+        admin_config, admin_header, forex_pair, init_app, temp_database, test_state,
+    };
+    use actix_web::{http::header, http::StatusCode, test};
+    use chrono::{DateTime, Duration, Utc};
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn tests_debug_state_reports_pair_count() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        db.insert(forex_pair(3, "USD/JPY", 151.2));
+
+        let app = init_app(test_state(db, admin_config())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/debug/state")
+            .insert_header(admin_header())
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body["pair_count"], 3);
+        assert_eq!(body["lock_poisoned"], false);
+        assert_eq!(body["lock_contended"], false);
+        assert_eq!(body["dirty"], false);
+        assert_eq!(body["circuit_breaker_state"], "closed");
+        assert_eq!(body.as_object().unwrap().len(), 5);
+    }
+
+    #[actix_web::test]
+    async fn tests_debug_state_requires_admin_token() {
+        let app = init_app(test_state(Database::new(), admin_config())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/debug/state")
+            .insert_header((header::AUTHORIZATION, "Bearer wrong"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn tests_audit_log_records_crud_events() {
+        let app = init_app(test_state(temp_database("audit"), admin_config())).await;
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "id": 1, "pair": "EUR/USD", "price": 1.08 }))
+            .to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::put()
+            .uri("/forex_pair")
+            .insert_header(admin_header())
+            .set_json(json!({ "id": 1, "pair": "EUR/USD", "price": 1.09 }))
+            .to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/1")
+            .to_request();
+        test::call_service(&app, req).await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/audit")
+            .insert_header(admin_header())
+            .to_request();
+        let entries: Value = test::call_and_read_body_json(&app, req).await;
+        let entries: &Vec<Value> = entries.as_array().unwrap();
+        let types: Vec<&str> = entries
+            .iter()
+            .map(|entry| entry["event_type"].as_str().unwrap())
+            .collect();
+        assert_eq!(types, ["created", "updated", "deleted"]);
+
+        let actors: Vec<&str> = entries
+            .iter()
+            .map(|entry| entry["actor"].as_str().unwrap())
+            .collect();
+        assert_eq!(actors, ["anonymous", "admin", "anonymous"]);
+
+        for entry in entries {
+            assert_eq!(entry["pair_id"], 1);
+            assert!(entry["id"].is_string());
+            assert!(entry["timestamp"].is_string());
+        }
+        assert_eq!(entries[1]["data"]["updated"]["before"]["price"], 1.08);
+        assert_eq!(entries[1]["data"]["updated"]["after"]["price"], 1.09);
+    }
+
+    #[actix_web::test]
+    async fn tests_maintenance_window_blocks_writes_until_it_ends() {
+        let start: DateTime<Utc> = Utc::now();
+        let config: Config = Config {
+            maintenance_windows: vec![MaintenanceWindow {
+                start,
+                end: start + Duration::milliseconds(800),
+            }],
+            ..admin_config()
+        };
+        let app = init_app(test_state(temp_database("maintenance"), config)).await;
+        let create = || {
+            test::TestRequest::post()
+                .uri("/forex_pair")
+                .set_json(json!({ "pair": "EUR/USD", "price": 1.08 }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, create()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "1");
+
+        // Reads keep working during the window
+        let req = test::TestRequest::get().uri("/forex_pairs").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/maintenance")
+            .insert_header(admin_header())
+            .to_request();
+        let status: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(status["read_only"], true);
+        assert_eq!(status["manual"], false);
+
+        tokio::time::sleep(std::time::Duration::from_millis(900)).await;
+        let resp = test::call_service(&app, create()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn tests_manual_read_only_toggle() {
+        let app = init_app(test_state(temp_database("read_only"), admin_config())).await;
+        let toggle = |read_only: bool| {
+            test::TestRequest::post()
+                .uri("/admin/maintenance")
+                .insert_header(admin_header())
+                .set_json(json!({ "read_only": read_only }))
+                .to_request()
+        };
+
+        let status: Value = test::call_and_read_body_json(&app, toggle(true)).await;
+        assert_eq!(status["read_only"], true);
+
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/1")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(resp.headers().get(header::RETRY_AFTER).is_none());
+
+        let status: Value = test::call_and_read_body_json(&app, toggle(false)).await;
+        assert_eq!(status["read_only"], false);
+    }
+
+    #[actix_web::test]
+    async fn tests_reload_picks_up_file_changes() {
+        let db: Database = temp_database("reload");
+        let path: std::path::PathBuf = db.database_path().to_path_buf();
+        let app = init_app(test_state(db, admin_config())).await;
+        let reload = || {
+            test::TestRequest::post()
+                .uri("/admin/reload")
+                .insert_header(admin_header())
+                .to_request()
+        };
+
+        let report: Value = test::call_and_read_body_json(&app, reload()).await;
+        assert_eq!(report["created"], true);
+        assert_eq!(report["pair_count"], 0);
+
+        let mut on_disk: Database = Database::with_path(&path);
+        on_disk.insert(forex_pair(1, "EUR/USD", 1.08));
+        on_disk.insert(forex_pair(2, "GBP/USD", 1.27));
+        on_disk.save_to_file().unwrap();
+        let report: Value = test::call_and_read_body_json(&app, reload()).await;
+        assert_eq!(report["created"], false);
+        assert_eq!(report["pair_count"], 2);
+
+        std::fs::write(&path, "{ not json").unwrap();
+        let resp = test::call_service(&app, reload()).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let req = test::TestRequest::get().uri("/forex_pair/2").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn tests_reindex_resorts_hand_edited_history() {
+        let path: std::path::PathBuf =
+            crate::test_support::temp_dir("reindex").join("database.json");
+        let edited: Value = json!({
+            "forex_pairs": {
+                "1": { "id": 1, "pair": "EUR/USD", "price": 1.09 },
+                "2": { "id": 2, "pair": "GBP/USD", "price": 1.27 }
+            },
+            "next_id": 3,
+            "price_history": {
+                "1": [
+                    { "timestamp": "2024-01-02T00:00:00Z", "price": 1.09 },
+                    { "timestamp": "2024-01-01T00:00:00Z", "price": 1.08 }
+                ]
+            }
+        });
+        std::fs::write(&path, edited.to_string()).unwrap();
+        let db: Database = Database::load_from_file(&path).unwrap();
+        let app = init_app(test_state(db, admin_config())).await;
+        let reindex = || {
+            test::TestRequest::post()
+                .uri("/admin/reindex")
+                .insert_header(admin_header())
+                .to_request()
+        };
+
+        let report: Value = test::call_and_read_body_json(&app, reindex()).await;
+        assert_eq!(report["resorted_history_ids"], json!([1]));
+        assert_eq!(report["pair_index_fixes"], 0);
+        let saved: Database = Database::load_from_file(&path).unwrap();
+        let prices: Vec<f64> = saved
+            .price_history(&1)
+            .iter()
+            .map(|point| point.price)
+            .collect();
+        assert_eq!(prices, vec![1.08, 1.09]);
+
+        // Nothing left to correct
+        let report: Value = test::call_and_read_body_json(&app, reindex()).await;
+        assert_eq!(report["resorted_history_ids"], json!([]));
+        assert_eq!(report["next_id_before"], report["next_id_after"]);
+    }
+
+    #[actix_web::test]
+    async fn tests_relocate_database_moves_the_file() {
+        let mut db: Database = temp_database("relocate");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.save_to_file().unwrap();
+        let old_path: std::path::PathBuf = db.database_path().to_path_buf();
+        let new_path: std::path::PathBuf = old_path.with_file_name("relocated.json");
+        let config: Config = Config {
+            database_path: old_path.clone(),
+            ..admin_config()
+        };
+        let app = init_app(test_state(db, config)).await;
+
+        // Paths outside the data directory are refused before anything moves
+        for escape in ["../escaped.json", "/tmp/escaped.json"] {
+            let req = test::TestRequest::post()
+                .uri("/admin/relocate_database")
+                .insert_header(admin_header())
+                .set_json(json!({ "path": escape }))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+        assert!(old_path.exists());
+
+        let req = test::TestRequest::post()
+            .uri("/admin/relocate_database")
+            .insert_header(admin_header())
+            .set_json(json!({ "path": "relocated.json" }))
+            .to_request();
+        let report: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["pair_count"], 1);
+        assert!(!old_path.exists());
+        assert_eq!(
+            Database::load_from_file(&new_path)
+                .unwrap()
+                .get(&1)
+                .unwrap()
+                .pair,
+            "EUR/USD"
+        );
+
+        // Later saves go to the new file
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "id": 2, "pair": "GBP/USD", "price": 1.27 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        assert_eq!(Database::load_from_file(&new_path).unwrap().len(), 2);
+        assert!(!old_path.exists());
+
+        std::fs::write(&old_path, "{}").unwrap();
+        let req = test::TestRequest::post()
+            .uri("/admin/relocate_database")
+            .insert_header(admin_header())
+            .set_json(json!({ "path": "database.json" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+}
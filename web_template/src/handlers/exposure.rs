@@ -0,0 +1,155 @@
+use crate::converter::{convert_rate, normalize_currency, Rate};
+use crate::database::{Database, ForexPair};
+use crate::error::AppError;
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// Derived rates come out of f64 arithmetic (1/150 = 0.006666666666666667), so anything
+// past this many places in a position value is float noise rather than money
+const VALUE_DECIMAL_PLACES: u32 = 8;
+
+#[derive(Deserialize, Debug)]
+pub struct ExposureQuery {
+    // Currency every position is valued in
+    pub base: String,
+}
+
+// `units` are held in the pair's base currency, e.g. 1000 EUR for EUR/USD.
+// Accepts a JSON number or a decimal string.
+#[derive(Deserialize, Debug)]
+pub struct Position {
+    pub pair: String,
+    pub units: Decimal,
+}
+
+// Decimals are serialised as strings so totals survive JSON clients without rounding
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum PositionValue {
+    Valued {
+        pair: String,
+        units: Decimal,
+        currency: String,
+        rate: Decimal,
+        value: Decimal,
+    },
+    Error {
+        pair: String,
+        error: String,
+    },
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExposureReport {
+    pub base: String,
+    pub positions: Vec<PositionValue>,
+    // Sum over the valued positions only
+    pub total: Decimal,
+    pub error_count: usize,
+}
+
+// Value a list of positions in one currency; unresolvable positions are reported, not fatal
+pub async fn exposure(
+    query: web::Query<ExposureQuery>,
+    app_state: web::Data<AppState>,
+    positions: web::Json<Vec<Position>>,
+) -> Result<HttpResponse, AppError> {
+    let base: String =
+        normalize_currency(&query.base).map_err(|e| AppError::BadRequest(e.to_string()))?;
+
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    let positions: Vec<PositionValue> = positions
+        .into_inner()
+        .into_iter()
+        .map(|position| match value_position(&db, &base, &position) {
+            Ok(valued) => valued,
+            Err(error) => PositionValue::Error {
+                pair: position.pair,
+                error,
+            },
+        })
+        .collect();
+    drop(db);
+
+    let mut total: Decimal = Decimal::ZERO;
+    let mut error_count: usize = 0;
+    for position in &positions {
+        match position {
+            PositionValue::Valued { value, .. } => total += *value,
+            PositionValue::Error { .. } => error_count += 1,
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(ExposureReport {
+        base,
+        positions,
+        total: total.normalize(),
+        error_count,
+    }))
+}
+
+fn value_position(db: &Database, base: &str, position: &Position) -> Result<PositionValue, String> {
+    let pair: String = ForexPair::normalize_pair(&position.pair)?;
+    let currency: String = pair.split('/').next().unwrap_or_default().to_string();
+    let rate: Rate = convert_rate(db, &currency, base).map_err(|e| e.to_string())?;
+    // from_f64 keeps the shortest decimal that round-trips, so 1.08 stays 1.08
+    let rate: Decimal = Decimal::from_f64(rate.rate)
+        .ok_or_else(|| format!("rate {} cannot be represented as a decimal", rate.rate))?;
+    let value: Decimal = position
+        .units
+        .checked_mul(rate)
+        .ok_or_else(|| "position value overflows".to_string())?;
+
+    Ok(PositionValue::Valued {
+        pair,
+        units: position.units,
+        currency,
+        rate: rate.normalize(),
+        value: value.round_dp(VALUE_DECIMAL_PLACES).normalize(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::test_support::{forex_pair, init_app, test_state};
+    use actix_web::{http::StatusCode, test};
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn tests_exposure_totals_positions_in_base() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.1));
+        db.insert(forex_pair(2, "GBP/USD", 1.3));
+        db.insert(forex_pair(3, "USD/JPY", 150.0));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::post()
+            .uri("/exposure?base=usd")
+            .set_json(json!([
+                { "pair": "EUR/USD", "units": 1000 },
+                { "pair": "gbp-usd", "units": "0.1" },
+                { "pair": "JPY/USD", "units": 300 },
+                { "pair": "USD/JPY", "units": 0.2 },
+                { "pair": "CHF/USD", "units": 10 },
+                { "pair": "nonsense", "units": 10 },
+            ]))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+
+        // 1100 + 0.13 + 2 (300 JPY via the inverse of 150) + 0.2; a float sum would drift
+        assert_eq!(body["positions"][0]["value"], "1100");
+        assert_eq!(body["positions"][1]["value"], "0.13");
+        assert_eq!(body["positions"][1]["pair"], "GBP/USD");
+        assert_eq!(body["total"], "1102.33");
+        assert_eq!(body["error_count"], 2);
+        assert!(body["positions"][4]["error"].is_string());
+        assert!(body["positions"][5]["error"].is_string());
+    }
+}
@@ -0,0 +1,1396 @@
+use crate::auth::{Actor, Admin};
+use crate::config::Config;
+use crate::converter::{cross_rate, invert, Rate};
+use crate::database::{Database, ForexPair, NormalizeReport, FOREX_PAIR_FIELDS};
+use crate::error::AppError;
+use crate::events::{FieldDiff, ForexPairEvent};
+use crate::locale::LocalizedForexPair;
+use crate::persistence::Snapshot;
+use crate::state::{AppState, WritePermit};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+// Validate, normalise the pair string and make sure no other record already owns it
+fn prepare_forex_pair(
+    db: &Database,
+    config: &Config,
+    mut forex_pair: ForexPair,
+) -> Result<ForexPair, AppError> {
+    forex_pair.pair = ForexPair::normalize_pair(&forex_pair.pair).map_err(AppError::BadRequest)?;
+    forex_pair.validate(config.max_spread_pct)?;
+    db.keep_created_at(&mut forex_pair);
+    match db.find_by_pair(&forex_pair.pair) {
+        Some(existing) if existing.id != forex_pair.id => Err(AppError::Conflict(format!(
+            "pair {} already exists with id {}",
+            forex_pair.pair, existing.id
+        ))),
+        _ => Ok(forex_pair),
+    }
+}
+
+// With config.strict_fields, a 400 naming every field of the body ForexPair does not declare
+fn reject_unknown_fields<'a>(
+    config: &Config,
+    fields: impl IntoIterator<Item = &'a String>,
+) -> Result<(), AppError> {
+    if !config.strict_fields {
+        return Ok(());
+    }
+    let mut unknown: Vec<String> = fields
+        .into_iter()
+        .filter(|field| !FOREX_PAIR_FIELDS.contains(&field.as_str()))
+        .map(|field| format!("`{}`", field))
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    unknown.sort();
+    Err(AppError::BadRequest(format!(
+        "unknown field{} {}, expected one of {}",
+        if unknown.len() == 1 { "" } else { "s" },
+        unknown.join(", "),
+        FOREX_PAIR_FIELDS.join(", ")
+    )))
+}
+
+// Extra fields of a body, i.e. the ones ForexPair does not declare
+fn body_fields(forex_pair: &ForexPair) -> impl Iterator<Item = &String> {
+    forex_pair
+        .extra_fields
+        .iter()
+        .flat_map(|fields| fields.keys())
+}
+
+// If-Unmodified-Since on PUT/PATCH/DELETE: 412 when the record was stored after the given
+// time. Compared in whole seconds, the resolution of an HTTP date, so a Last-Modified value
+// sent back as is passes. Takes an HTTP date or RFC 3339; records with no known update time
+// and new ids always pass.
+fn check_unmodified_since(
+    req: &HttpRequest,
+    updated_at: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    let Some(raw) = req.headers().get(header::IF_UNMODIFIED_SINCE) else {
+        return Ok(());
+    };
+    let raw: &str = raw.to_str().unwrap_or_default().trim();
+    let since: DateTime<Utc> = DateTime::parse_from_rfc2822(raw)
+        .or_else(|_| DateTime::parse_from_rfc3339(raw))
+        .map(|since| since.with_timezone(&Utc))
+        .map_err(|_| {
+            AppError::BadRequest(format!("If-Unmodified-Since {:?} is not a date", raw))
+        })?;
+    match updated_at {
+        Some(updated_at) if updated_at.timestamp() > since.timestamp() => {
+            Err(AppError::PreconditionFailed(format!(
+                "record was modified at {}, after {}",
+                updated_at.to_rfc3339(),
+                since.to_rfc3339()
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Created when the id is new, otherwise an update of the previous record
+fn upsert_event(
+    actor: &Actor,
+    previous: Option<&ForexPair>,
+    current: &ForexPair,
+) -> ForexPairEvent {
+    match previous {
+        Some(previous) => ForexPairEvent::updated(&actor.0, previous, current),
+        None => ForexPairEvent::created(&actor.0, current),
+    }
+}
+
+pub async fn create_forex_pair(
+    actor: Actor,
+    app_state: web::Data<AppState>,
+    forex_pair: web::Json<ForexPair>,
+) -> Result<HttpResponse, AppError> {
+    reject_unknown_fields(&app_state.config, body_fields(&forex_pair))?;
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    // Scoped so the data lock is released before the file is written
+    let (forex_pair, previous, snapshot): (ForexPair, Option<ForexPair>, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let mut forex_pair: ForexPair =
+            prepare_forex_pair(&db, &app_state.config, forex_pair.into_inner())?;
+        if forex_pair.id == 0 {
+            forex_pair.id = db.allocate_id();
+        }
+        let previous: Option<ForexPair> = db.insert(forex_pair.clone());
+        (forex_pair, previous, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    app_state.publish(upsert_event(&actor, previous.as_ref(), &forex_pair));
+    Ok(HttpResponse::Ok().json(forex_pair))
+}
+
+// Create the pair only if no record has its name yet: 201 with the new record, otherwise 200
+// with the existing one untouched. Safe to repeat in provisioning scripts.
+pub async fn ensure_forex_pair(
+    actor: Actor,
+    app_state: web::Data<AppState>,
+    forex_pair: web::Json<ForexPair>,
+) -> Result<HttpResponse, AppError> {
+    reject_unknown_fields(&app_state.config, body_fields(&forex_pair))?;
+    let mut forex_pair: ForexPair = forex_pair.into_inner();
+    forex_pair.pair = ForexPair::normalize_pair(&forex_pair.pair).map_err(AppError::BadRequest)?;
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (forex_pair, snapshot): (ForexPair, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        if let Some(existing) = db.find_by_pair(&forex_pair.pair) {
+            return Ok(HttpResponse::Ok().json(existing));
+        }
+        if let Some(existing) = db.get(&forex_pair.id) {
+            return Err(AppError::Conflict(format!(
+                "id {} is already used by {}",
+                existing.id, existing.pair
+            )));
+        }
+        let mut forex_pair: ForexPair = prepare_forex_pair(&db, &app_state.config, forex_pair)?;
+        if forex_pair.id == 0 {
+            forex_pair.id = db.allocate_id();
+        }
+        db.insert(forex_pair.clone());
+        (forex_pair, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    app_state.publish(ForexPairEvent::created(&actor.0, &forex_pair));
+    Ok(HttpResponse::Created().json(forex_pair))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LocaleQuery {
+    // e.g. de-DE; adds formatted_price next to the numeric price
+    pub locale: Option<String>,
+}
+
+impl LocaleQuery {
+    // The request's locale, else the configured default
+    fn resolve<'a>(&'a self, config: &'a Config) -> Option<&'a str> {
+        self.locale
+            .as_deref()
+            .or(config.default_locale.as_deref())
+            .filter(|locale| !locale.trim().is_empty())
+    }
+}
+
+pub async fn read_forex_pair(
+    app_state: web::Data<AppState>,
+    id: web::Path<u64>,
+    query: web::Query<LocaleQuery>,
+) -> impl Responder {
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    let id: u64 = id.into_inner();
+    let Some(forex_pair) = db.get(&id) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let mut response = HttpResponse::Ok();
+    if let Some(updated_at) = db.updated_at(&id) {
+        response.insert_header((
+            header::LAST_MODIFIED,
+            header::HttpDate::from(std::time::SystemTime::from(updated_at)),
+        ));
+    }
+    match query.resolve(&app_state.config) {
+        Some(locale) => response.json(LocalizedForexPair::new(forex_pair, locale)),
+        None => response.json(forex_pair),
+    }
+}
+
+// A record looked up by pair name; inverse marks one computed from the stored direction
+#[derive(Serialize, Debug)]
+pub struct PairLookup {
+    #[serde(flatten)]
+    pub forex_pair: ForexPair,
+    pub inverse: bool,
+}
+
+// GET /forex_pairs/by_pair/EUR/USD, any spelling normalize_pair accepts. With
+// serve_inverse_pairs a missing USD/EUR is answered from a stored EUR/USD; nothing is saved.
+pub async fn read_forex_pair_by_pair(
+    app_state: web::Data<AppState>,
+    pair: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let pair: String = ForexPair::normalize_pair(&pair).map_err(AppError::BadRequest)?;
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    if let Some(forex_pair) = db.find_by_pair(&pair) {
+        return Ok(HttpResponse::Ok().json(PairLookup {
+            forex_pair: forex_pair.clone(),
+            inverse: false,
+        }));
+    }
+
+    let not_found = || AppError::NotFound(format!("no forex pair {}", pair));
+    if !app_state.config.serve_inverse_pairs {
+        return Err(not_found());
+    }
+    let (base, quote) = pair.split_once('/').ok_or_else(not_found)?;
+    let stored: &ForexPair = db
+        .find_by_pair(&format!("{}/{}", quote, base))
+        .ok_or_else(not_found)?;
+    let inverse: ForexPair = invert(stored).ok_or_else(|| {
+        AppError::NotFound(format!(
+            "no forex pair {}, and {} has no usable price to invert",
+            pair, stored.pair
+        ))
+    })?;
+    Ok(HttpResponse::Ok().json(PairLookup {
+        forex_pair: inverse,
+        inverse: true,
+    }))
+}
+
+pub const DEFAULT_OLDEST: usize = 5;
+pub const MAX_OLDEST: usize = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct OldestQuery {
+    pub n: Option<usize>,
+}
+
+// GET /forex_pairs/oldest?n=5: the records created longest ago, to spot stale data
+pub async fn read_oldest(
+    app_state: web::Data<AppState>,
+    query: web::Query<OldestQuery>,
+) -> Result<HttpResponse, AppError> {
+    let n: usize = query.n.unwrap_or(DEFAULT_OLDEST);
+    if n > MAX_OLDEST {
+        return Err(AppError::BadRequest(format!(
+            "n must be at most {}",
+            MAX_OLDEST
+        )));
+    }
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    Ok(HttpResponse::Ok().json(db.oldest_pairs(n)))
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChecksumResponse {
+    pub checksum: String,
+    pub pair_count: usize,
+}
+
+// Database::checksum, also sent as the ETag. A mirror that sends it back in If-None-Match
+// gets an empty 304 while nothing has changed.
+pub async fn read_checksum(req: HttpRequest, app_state: web::Data<AppState>) -> HttpResponse {
+    let (checksum, pair_count): (String, usize) = {
+        let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        (db.checksum(), db.len())
+    };
+    let etag: header::EntityTag = header::EntityTag::new_strong(checksum.clone());
+    let unchanged: bool = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .filter_map(|tag| tag.trim().parse::<header::EntityTag>().ok())
+                .any(|tag| tag.weak_eq(&etag))
+        });
+    if unchanged {
+        return HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .finish();
+    }
+    HttpResponse::Ok()
+        .insert_header(header::ETag(etag))
+        .json(ChecksumResponse {
+            checksum,
+            pair_count,
+        })
+}
+
+pub const DEFAULT_NEIGHBORS: usize = 5;
+pub const MAX_NEIGHBORS: usize = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct NeighborsQuery {
+    pub n: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PriceNeighbor<'a> {
+    #[serde(flatten)]
+    pub forex_pair: &'a ForexPair,
+    // Absolute price difference to the requested pair
+    pub distance: f64,
+}
+
+// The ?n= pairs priced closest to {id}, closest first, {id} itself excluded
+pub async fn read_neighbors(
+    app_state: web::Data<AppState>,
+    id: web::Path<u64>,
+    query: web::Query<NeighborsQuery>,
+) -> Result<HttpResponse, AppError> {
+    let id: u64 = id.into_inner();
+    let n: usize = query.n.unwrap_or(DEFAULT_NEIGHBORS);
+    if n > MAX_NEIGHBORS {
+        return Err(AppError::BadRequest(format!(
+            "n must be at most {}",
+            MAX_NEIGHBORS
+        )));
+    }
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    let origin: &ForexPair = db
+        .get(&id)
+        .ok_or_else(|| AppError::NotFound(format!("no forex pair with id {}", id)))?;
+    let neighbors: Vec<PriceNeighbor> = db
+        .nearest_by_price(id, n)
+        .into_iter()
+        .map(|forex_pair| PriceNeighbor {
+            forex_pair,
+            distance: (forex_pair.price - origin.price).abs(),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(neighbors))
+}
+
+// A stored pair whose cross rate goes through the requested one
+#[derive(Serialize, Debug)]
+pub struct Dependent {
+    pub id: u64,
+    pub pair: String,
+    // How the pair is priced without its own record, via the requested one
+    pub cross_rate: Rate,
+}
+
+// Stored pairs whose cross rate goes through {id}, with the path of each
+pub async fn read_dependencies(
+    app_state: web::Data<AppState>,
+    id: web::Path<u64>,
+) -> Result<HttpResponse, AppError> {
+    let id: u64 = id.into_inner();
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    if db.get(&id).is_none() {
+        return Err(AppError::NotFound(format!("no forex pair with id {}", id)));
+    }
+    let dependents: Vec<Dependent> = db
+        .compute_dependencies(id)
+        .into_iter()
+        .filter_map(|dependent_id| {
+            let forex_pair: &ForexPair = db.get(&dependent_id)?;
+            Some(Dependent {
+                id: dependent_id,
+                pair: forex_pair.pair.clone(),
+                cross_rate: cross_rate(&db, forex_pair)?,
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(dependents))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct IdRangeQuery {
+    // Both ends inclusive; either may be left out
+    pub from_id: Option<u64>,
+    pub to_id: Option<u64>,
+}
+
+pub async fn read_all_forex_pairs(
+    app_state: web::Data<AppState>,
+    query: web::Query<LocaleQuery>,
+    range: web::Query<IdRangeQuery>,
+) -> impl Responder {
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    let forex_pairs: Vec<&ForexPair> = match (range.from_id, range.to_id) {
+        (None, None) => db.get_all(),
+        (from_id, to_id) => db.get_range(from_id.unwrap_or(0), to_id.unwrap_or(u64::MAX)),
+    };
+    match query.resolve(&app_state.config) {
+        Some(locale) => HttpResponse::Ok().json(
+            forex_pairs
+                .into_iter()
+                .map(|forex_pair| LocalizedForexPair::new(forex_pair, locale))
+                .collect::<Vec<LocalizedForexPair>>(),
+        ),
+        None => HttpResponse::Ok().json(forex_pairs),
+    }
+}
+
+pub async fn update_forex_pair(
+    actor: Actor,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    forex_pair: web::Json<ForexPair>,
+) -> Result<HttpResponse, AppError> {
+    reject_unknown_fields(&app_state.config, body_fields(&forex_pair))?;
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (forex_pair, previous, snapshot): (ForexPair, Option<ForexPair>, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let forex_pair: ForexPair =
+            prepare_forex_pair(&db, &app_state.config, forex_pair.into_inner())?;
+        check_unmodified_since(&req, db.updated_at(&forex_pair.id))?;
+        let previous: Option<ForexPair> = db.get(&forex_pair.id).cloned();
+        db.update(forex_pair.clone());
+        (forex_pair, previous, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    app_state.publish(upsert_event(&actor, previous.as_ref(), &forex_pair));
+    Ok(HttpResponse::Ok().finish())
+}
+
+// Apply the given fields over the stored record, e.g. `{"price": 1.09}`. A null removes an
+// optional field. The audit log gets only the fields that actually changed.
+pub async fn patch_forex_pair(
+    actor: Actor,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    id: web::Path<u64>,
+    patch: web::Json<Map<String, Value>>,
+) -> Result<HttpResponse, AppError> {
+    let id: u64 = id.into_inner();
+    let patch: Map<String, Value> = patch.into_inner();
+    reject_unknown_fields(&app_state.config, patch.keys())?;
+    if patch
+        .get("id")
+        .is_some_and(|value| value.as_u64() != Some(id))
+    {
+        return Err(AppError::BadRequest(
+            "id cannot be changed by a patch".to_string(),
+        ));
+    }
+
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (patched, changes, snapshot): (ForexPair, Vec<FieldDiff>, Option<Snapshot>) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let previous: ForexPair = db
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("no forex pair with id {}", id)))?;
+        check_unmodified_since(&req, db.updated_at(&id))?;
+        let mut fields: Map<String, Value> = match Value::from(previous.clone()) {
+            Value::Object(fields) => fields,
+            _ => Map::new(),
+        };
+        for (field, value) in patch {
+            if value.is_null() {
+                fields.remove(&field);
+            } else {
+                fields.insert(field, value);
+            }
+        }
+        let patched: ForexPair = ForexPair::try_from(Value::Object(fields))
+            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+        let patched: ForexPair = prepare_forex_pair(&db, &app_state.config, patched)?;
+        let changes: Vec<FieldDiff> = FieldDiff::between(&previous, &patched);
+        let snapshot: Option<Snapshot> = if changes.is_empty() {
+            None
+        } else {
+            db.update(patched.clone());
+            Some(app_state.snapshot(&db)?)
+        };
+        (patched, changes, snapshot)
+    };
+    if let Some(snapshot) = snapshot {
+        app_state.persist(snapshot).await?;
+        app_state.publish(ForexPairEvent::patched(&actor.0, id, changes));
+    }
+    Ok(HttpResponse::Ok().json(patched))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RenameRequest {
+    pub id: u64,
+    pub new_pair: String,
+}
+
+// Change a record's pair string (e.g. USD/CNY -> USD/CNH) keeping its id and history
+pub async fn rename_forex_pair(
+    actor: Actor,
+    app_state: web::Data<AppState>,
+    rename: web::Json<RenameRequest>,
+) -> Result<HttpResponse, AppError> {
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (previous, renamed, snapshot): (ForexPair, ForexPair, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let mut renamed: ForexPair = db
+            .get(&rename.id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("no forex pair with id {}", rename.id)))?;
+        renamed.pair = rename.new_pair.clone();
+        let renamed: ForexPair = prepare_forex_pair(&db, &app_state.config, renamed)?;
+        let previous: ForexPair = db
+            .rename(renamed.id, &renamed.pair)
+            .expect("record was found under the same lock");
+        (previous, renamed, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    app_state.publish(ForexPairEvent::updated(&actor.0, &previous, &renamed));
+    Ok(HttpResponse::Ok().json(renamed))
+}
+
+pub async fn delete_forex_pair(
+    actor: Actor,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    id: web::Path<u64>,
+) -> Result<HttpResponse, AppError> {
+    let id: u64 = id.into_inner();
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (removed, snapshot): (Option<ForexPair>, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        check_unmodified_since(&req, db.updated_at(&id))?;
+        let removed: Option<ForexPair> = db.delete(&id);
+        (removed, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    if let Some(removed) = removed {
+        app_state.publish(ForexPairEvent::deleted(&actor.0, &removed));
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+// Bring legacy records in line with the normalised pair format, saving once
+pub async fn rebalance_forex_pairs(
+    _admin: Admin,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (report, snapshot): (NormalizeReport, Option<Snapshot>) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let report: NormalizeReport = db.normalize_pairs();
+        let snapshot: Option<Snapshot> = match report.changed_count {
+            0 => None,
+            _ => Some(app_state.snapshot(&db)?),
+        };
+        (report, snapshot)
+    };
+    if let Some(snapshot) = snapshot {
+        app_state.persist(snapshot).await?;
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Both the body and the header must carry this before ids are renumbered
+pub const RECALCULATE_IDS_CONFIRMATION: &str = "recalculate_ids";
+pub const CONFIRM_HEADER: &str = "X-Confirm";
+
+#[derive(Deserialize, Debug)]
+pub struct RecalculateIdsRequest {
+    #[serde(default)]
+    pub confirm: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RecalculateIdsReport {
+    pub pair_count: usize,
+    // Old id -> new id
+    pub mapping: BTreeMap<u64, u64>,
+}
+
+// Renumber every pair from 1 in ascending price order. Clients holding ids will point at
+// other records afterwards, hence the double confirmation.
+pub async fn recalculate_ids(
+    _admin: Admin,
+    actor: Actor,
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Json<RecalculateIdsRequest>,
+) -> Result<HttpResponse, AppError> {
+    let header_confirm: Option<&str> = req
+        .headers()
+        .get(CONFIRM_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if body.confirm != RECALCULATE_IDS_CONFIRMATION
+        || header_confirm != Some(RECALCULATE_IDS_CONFIRMATION)
+    {
+        return Err(AppError::BadRequest(format!(
+            "renumbering ids needs \"confirm\": {:?} in the body and {}: {} as a header",
+            RECALCULATE_IDS_CONFIRMATION, CONFIRM_HEADER, RECALCULATE_IDS_CONFIRMATION
+        )));
+    }
+
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (report, snapshot): (RecalculateIdsReport, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let mapping: BTreeMap<u64, u64> = db.recalculate_ids();
+        let report: RecalculateIdsReport = RecalculateIdsReport {
+            pair_count: db.len(),
+            mapping,
+        };
+        (report, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    app_state.publish(ForexPairEvent::ids_recalculated(
+        &actor.0,
+        report.mapping.clone(),
+    ));
+    tracing::info!(pairs = report.pair_count, "recalculated ids");
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClearHistoryReport {
+    pub id: u64,
+    pub removed: usize,
+}
+
+// Purge a pair's price history without touching the pair itself
+pub async fn clear_forex_pair_history(
+    _admin: Admin,
+    app_state: web::Data<AppState>,
+    id: web::Path<u64>,
+) -> Result<HttpResponse, AppError> {
+    let id: u64 = id.into_inner();
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (removed, snapshot): (usize, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        if db.get(&id).is_none() {
+            return Err(AppError::NotFound(format!("no forex pair with id {}", id)));
+        }
+        let removed: usize = db.clear_history(id);
+        (removed, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    tracing::info!(id, removed, "cleared price history");
+    Ok(HttpResponse::Ok().json(ClearHistoryReport { id, removed }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::ForexPair;
+    use crate::database::{Database, IdStrategy};
+    use crate::test_support::{
+        admin_config, admin_header, forex_pair, init_app, temp_database, test_state,
+    };
+    use actix_web::{http::header, http::StatusCode, test};
+    use chrono::{DateTime, Utc};
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn tests_create_normalizes_and_rejects_duplicates() {
+        let app = init_app(test_state(temp_database("create"), admin_config())).await;
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "id": 1, "pair": "eurusd", "price": 1.08 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let stored: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(stored["pair"], "EUR/USD");
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "id": 2, "pair": "EUR-USD", "price": 1.08 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "id": 3, "pair": "nonsense", "price": 1.0 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "pair": "AUD/USD", "price": "0.66" }))
+            .to_request();
+        let created: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(created["price"], 0.66);
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "pair": "NZD/USD", "price": "sixty" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "pair": "GBP/USD", "price": 1.0, "bid": 0.001, "ask": 99999.0 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn tests_create_assigns_ids_with_configured_strategy() {
+        for strategy in [IdStrategy::Sequential, IdStrategy::Random] {
+            let config: Config = Config {
+                id_strategy: strategy,
+                ..Config::default()
+            };
+            let app = init_app(test_state(temp_database("id_strategy"), config)).await;
+
+            let mut ids: Vec<u64> = vec![];
+            for pair in ["EUR/USD", "GBP/USD", "USD/JPY"] {
+                let req = test::TestRequest::post()
+                    .uri("/forex_pair")
+                    .set_json(json!({ "pair": pair, "price": 1.0 }))
+                    .to_request();
+                let created: Value = test::call_and_read_body_json(&app, req).await;
+                let id: u64 = created["id"].as_u64().unwrap();
+
+                let req = test::TestRequest::get()
+                    .uri(&format!("/forex_pair/{}", id))
+                    .to_request();
+                let stored: Value = test::call_and_read_body_json(&app, req).await;
+                assert_eq!(stored["pair"], pair);
+                ids.push(id);
+            }
+
+            match strategy {
+                IdStrategy::Sequential => assert_eq!(ids, vec![1, 2, 3]),
+                IdStrategy::Random => {
+                    assert_ne!(ids, vec![1, 2, 3]);
+                    assert!(ids[0] != ids[1] && ids[1] != ids[2] && ids[0] != ids[2]);
+                }
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_rebalance_normalizes_legacy_pairs() {
+        let mut db: Database = temp_database("rebalance");
+        db.insert(forex_pair(1, "eur/usd", 1.08));
+        db.insert(forex_pair(2, "Gbp-Usd", 1.27));
+        db.insert(forex_pair(3, "usdjpy", 151.2));
+        db.insert(forex_pair(4, "AUD/USD", 0.66));
+        let path = db.database_path().to_path_buf();
+        let app = init_app(test_state(db, admin_config())).await;
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/rebalance")
+            .insert_header(admin_header())
+            .to_request();
+        let report: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["changed_count"], 3);
+        assert_eq!(report["unchanged_count"], 1);
+
+        let saved: Database = Database::load_from_file(&path).unwrap();
+        for (id, pair) in [
+            (1, "EUR/USD"),
+            (2, "GBP/USD"),
+            (3, "USD/JPY"),
+            (4, "AUD/USD"),
+        ] {
+            assert_eq!(saved.get(&id).unwrap().pair, pair);
+            assert_eq!(saved.find_by_pair(pair).unwrap().id, id);
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_recalculate_ids_needs_both_confirmations() {
+        let mut db: Database = temp_database("recalculate_ids");
+        db.insert(forex_pair(12, "USD/JPY", 151.2));
+        for price in [1.08, 1.09] {
+            db.insert(forex_pair(30, "EUR/USD", price));
+        }
+        db.insert(forex_pair(7, "AUD/USD", 0.66));
+        let path = db.database_path().to_path_buf();
+        let state = test_state(db, admin_config());
+        let app = init_app(state.clone()).await;
+
+        for (body, header) in [
+            (json!({}), Some("recalculate_ids")),
+            (json!({ "confirm": "recalculate_ids" }), None),
+            (json!({ "confirm": "yes" }), Some("yes")),
+        ] {
+            let mut req = test::TestRequest::post()
+                .uri("/forex_pairs/recalculate_ids")
+                .insert_header(admin_header())
+                .set_json(body);
+            if let Some(header) = header {
+                req = req.insert_header(("X-Confirm", header));
+            }
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+        assert!(state.db.lock().unwrap().get(&30).is_some());
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/recalculate_ids")
+            .insert_header(admin_header())
+            .insert_header(("X-Confirm", "recalculate_ids"))
+            .set_json(json!({ "confirm": "recalculate_ids" }))
+            .to_request();
+        let report: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["pair_count"], 3);
+        assert_eq!(report["mapping"], json!({ "7": 1, "12": 3, "30": 2 }));
+
+        let saved: Database = Database::load_from_file(&path).unwrap();
+        for (id, pair) in [(1, "AUD/USD"), (2, "EUR/USD"), (3, "USD/JPY")] {
+            assert_eq!(saved.get(&id).unwrap().pair, pair);
+            assert_eq!(saved.find_by_pair(pair).unwrap().id, id);
+        }
+        let prices: Vec<f64> = saved
+            .price_history(&2)
+            .iter()
+            .map(|point| point.price)
+            .collect();
+        assert_eq!(prices, vec![1.08, 1.09]);
+        assert_eq!(state.audit.entries().last().unwrap().pair_id, 0);
+    }
+
+    #[actix_web::test]
+    async fn tests_clear_history_keeps_the_pair() {
+        let mut db: Database = temp_database("clear_history");
+        for price in [1.08, 1.09, 1.1] {
+            db.insert(forex_pair(1, "EUR/USD", price));
+        }
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        let path = db.database_path().to_path_buf();
+        let state = test_state(db, admin_config());
+        let app = init_app(state.clone()).await;
+
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/1/history")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/1/history")
+            .insert_header(admin_header())
+            .to_request();
+        let report: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["removed"], 3);
+
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let stored: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(stored["price"], 1.1);
+        {
+            let db = state.db.lock().unwrap();
+            assert!(db.price_history(&1).is_empty());
+            assert_eq!(db.price_history(&2).len(), 1);
+        }
+        let saved: Database = Database::load_from_file(&path).unwrap();
+        assert!(saved.price_history(&1).is_empty());
+        assert_eq!(saved.get(&1).unwrap().price, 1.1);
+
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/9/history")
+            .insert_header(admin_header())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn tests_locale_adds_formatted_price() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "USD/JPY", 1234.5));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/1?locale=en-US")
+            .to_request();
+        let en: Value = test::call_and_read_body_json(&app, req).await;
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/1?locale=de-DE")
+            .to_request();
+        let de: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(en["formatted_price"], "1,234.5");
+        assert_eq!(de["formatted_price"], "1.234,5");
+        assert_eq!(en["price"], 1234.5);
+        assert_eq!(de["price"], 1234.5);
+
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let plain: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(plain.get("formatted_price").is_none());
+
+        // The configured default applies when the request names no locale
+        let config: Config = Config {
+            default_locale: Some("de-DE".to_string()),
+            ..Config::default()
+        };
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "USD/JPY", 1234.5));
+        let app = init_app(test_state(db, config)).await;
+        let req = test::TestRequest::get().uri("/forex_pairs").to_request();
+        let all: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(all[0]["formatted_price"], "1.234,5");
+    }
+
+    #[actix_web::test]
+    async fn tests_patch_logs_only_changed_fields() {
+        let mut db: Database = temp_database("patch");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let app = init_app(test_state(db, admin_config())).await;
+        let patch = |body: Value| {
+            test::TestRequest::patch()
+                .uri("/forex_pair/1")
+                .set_json(body)
+                .to_request()
+        };
+
+        let patched: Value =
+            test::call_and_read_body_json(&app, patch(json!({ "price": 1.09 }))).await;
+        assert_eq!(patched["price"], 1.09);
+        assert_eq!(patched["pair"], "EUR/USD");
+
+        // Nothing changes, nothing is logged
+        let resp = test::call_service(&app, patch(json!({ "price": 1.09 }))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(&app, patch(json!({ "id": 2 }))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, patch(json!({ "price": "high" }))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/audit")
+            .insert_header(admin_header())
+            .to_request();
+        let entries: Value = test::call_and_read_body_json(&app, req).await;
+        let entries: &Vec<Value> = entries.as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["event_type"], "patched");
+        assert_eq!(
+            entries[0]["data"]["patched"]["changes"],
+            json!([{ "field": "price", "old_value": 1.08, "new_value": 1.09 }])
+        );
+    }
+
+    #[actix_web::test]
+    async fn tests_rename_keeps_id_and_history() {
+        let mut db: Database = temp_database("rename");
+        db.insert(forex_pair(42, "USD/CNY", 7.1));
+        db.insert(forex_pair(42, "USD/CNY", 7.2));
+        db.insert(forex_pair(43, "EUR/USD", 1.08));
+        db.add_to_watchlist("alice", "USD/CNY");
+        let state = test_state(db, admin_config());
+        let app = init_app(state.clone()).await;
+        let rename = |id: u64, new_pair: &str| {
+            test::TestRequest::post()
+                .uri("/forex_pairs/rename")
+                .set_json(json!({ "id": id, "new_pair": new_pair }))
+                .to_request()
+        };
+
+        let renamed: Value = test::call_and_read_body_json(&app, rename(42, "usd-cnh")).await;
+        assert_eq!(renamed["id"], 42);
+        assert_eq!(renamed["pair"], "USD/CNH");
+
+        let req = test::TestRequest::get().uri("/forex_pair/42").to_request();
+        let stored: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(stored["pair"], "USD/CNH");
+        {
+            let db = state.db.lock().unwrap();
+            assert_eq!(db.find_by_pair("USD/CNH").unwrap().id, 42);
+            assert!(db.find_by_pair("USD/CNY").is_none());
+            assert_eq!(db.price_history(&42).len(), 2);
+            assert_eq!(db.watchlist("alice")[0].pair, "USD/CNH");
+        }
+
+        let resp = test::call_service(&app, rename(42, "EUR/USD")).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let resp = test::call_service(&app, rename(42, "nonsense")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, rename(9, "USD/CNY")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/audit")
+            .insert_header(admin_header())
+            .to_request();
+        let entries: Value = test::call_and_read_body_json(&app, req).await;
+        let entries: &Vec<Value> = entries.as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["data"]["updated"]["before"]["pair"], "USD/CNY");
+    }
+
+    #[actix_web::test]
+    async fn tests_read_all_by_id_range() {
+        let mut db: Database = Database::new();
+        for (id, pair) in [
+            (99, "EUR/USD"),
+            (100, "GBP/USD"),
+            (150, "USD/JPY"),
+            (200, "AUD/USD"),
+            (201, "USD/CHF"),
+        ] {
+            db.insert(forex_pair(id, pair, 1.0));
+        }
+        let app = init_app(test_state(db, Config::default())).await;
+        let ids = |body: Value| -> Vec<u64> {
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|forex_pair| forex_pair["id"].as_u64().unwrap())
+                .collect()
+        };
+
+        for (uri, expected) in [
+            ("/forex_pairs?from_id=100&to_id=200", vec![100, 150, 200]),
+            ("/forex_pairs?from_id=150", vec![150, 200, 201]),
+            ("/forex_pairs?to_id=99", vec![99]),
+            ("/forex_pairs?from_id=151&to_id=199", vec![]),
+            ("/forex_pairs?from_id=200&to_id=100", vec![]),
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(ids(body), expected, "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_ensure_creates_once() {
+        let state = test_state(temp_database("ensure"), Config::default());
+        let app = init_app(state.clone()).await;
+        let ensure = |body: Value| {
+            test::TestRequest::put()
+                .uri("/forex_pairs/ensure")
+                .set_json(body)
+                .to_request()
+        };
+
+        let resp =
+            test::call_service(&app, ensure(json!({ "pair": "eurusd", "price": 1.08 }))).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let created: Value = test::read_body_json(resp).await;
+        assert_eq!(created["pair"], "EUR/USD");
+
+        // Present already: returned unchanged, the new price is ignored
+        let resp =
+            test::call_service(&app, ensure(json!({ "pair": "EUR/USD", "price": 2.0 }))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let existing: Value = test::read_body_json(resp).await;
+        assert_eq!(existing, created);
+        assert_eq!(state.db.lock().unwrap().len(), 1);
+
+        let resp = test::call_service(
+            &app,
+            ensure(json!({ "id": created["id"], "pair": "GBP/USD", "price": 1.27 })),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn tests_neighbors_lists_closest_prices() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        db.insert(forex_pair(3, "AUD/USD", 0.66));
+        db.insert(forex_pair(4, "USD/JPY", 151.2));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/1/neighbors?n=2")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body[0]["id"], 2);
+        assert_eq!(body[1]["id"], 3);
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert!((body[0]["distance"].as_f64().unwrap() - 0.19).abs() < 1e-9);
+
+        for (uri, status) in [
+            ("/forex_pair/9/neighbors", StatusCode::NOT_FOUND),
+            ("/forex_pair/1/neighbors?n=1000", StatusCode::BAD_REQUEST),
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_dependencies_lists_cross_rate_dependents() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.25));
+        db.insert(forex_pair(2, "USD/JPY", 150.0));
+        db.insert(forex_pair(3, "EUR/JPY", 187.5));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/2/dependencies")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        // EUR/USD also routes through USD/JPY, via EUR/JPY
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert_eq!(body[0]["id"], 1);
+        assert_eq!(body[1]["id"], 3);
+        assert_eq!(body[1]["pair"], "EUR/JPY");
+        assert_eq!(body[1]["cross_rate"]["path"], serde_json::json!([1, 2]));
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/9/dependencies")
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[actix_web::test]
+    async fn tests_by_pair_serves_stored_and_inverse_pairs() {
+        let mut db: Database = Database::new();
+        let mut eur_usd = forex_pair(1, "EUR/USD", 1.25);
+        eur_usd.bid = Some(1.24);
+        eur_usd.ask = Some(1.26);
+        db.insert(eur_usd);
+        db.insert(forex_pair(2, "XAU/BTC", 0.0));
+        let config: Config = Config {
+            serve_inverse_pairs: true,
+            ..Config::default()
+        };
+        let state = test_state(db, config);
+        let app = init_app(state.clone()).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/by_pair/eur-usd")
+            .to_request();
+        let stored: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(stored["price"], 1.25);
+        assert_eq!(stored["inverse"], false);
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/by_pair/USD/EUR")
+            .to_request();
+        let inverse: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(inverse["pair"], "USD/EUR");
+        assert_eq!(inverse["price"], 0.8);
+        assert_eq!(inverse["bid"], 1.0 / 1.26);
+        assert_eq!(inverse["ask"], 1.0 / 1.24);
+        assert_eq!(inverse["id"], 1);
+        assert_eq!(inverse["inverse"], true);
+        assert_eq!(state.db.lock().unwrap().len(), 2);
+
+        // Nothing to invert
+        for uri in [
+            "/forex_pairs/by_pair/BTC/XAU",
+            "/forex_pairs/by_pair/GBP/USD",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        }
+
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.25));
+        let app = init_app(test_state(db, Config::default())).await;
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/by_pair/USD/EUR")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn tests_strict_fields_rejects_unknown_fields() {
+        let config: Config = Config {
+            strict_fields: true,
+            ..Config::default()
+        };
+        let state = test_state(temp_database("strict_fields"), config);
+        let app = init_app(state.clone()).await;
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "pair": "EUR/USD", "price": 1.08, "prce": 1.09 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(resp).await;
+        assert!(body["message"].as_str().unwrap().contains("`prce`"));
+        assert!(state.db.lock().unwrap().is_empty());
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "id": 1, "pair": "EUR/USD", "price": 1.08 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::patch()
+            .uri("/forex_pair/1")
+            .set_json(json!({ "price": 1.09, "venue": "LMAX" }))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        // Lenient by default: unknown fields are kept as extra fields
+        let app = init_app(test_state(
+            temp_database("lenient_fields"),
+            Config::default(),
+        ))
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "pair": "EUR/USD", "price": 1.08, "venue": "LMAX" }))
+            .to_request();
+        let created: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(created["venue"], "LMAX");
+    }
+
+    #[actix_web::test]
+    async fn tests_if_unmodified_since_guards_writes() {
+        let mut db: Database = temp_database("unmodified_since");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        let app = init_app(test_state(db, admin_config())).await;
+
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let resp = test::call_service(&app, req).await;
+        let last_modified: String = resp
+            .headers()
+            .get(header::LAST_MODIFIED)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let an_hour_ago: String = (Utc::now() - chrono::Duration::hours(1)).to_rfc2822();
+
+        // Failed: the records were stored after the given time, nothing changes
+        let req = test::TestRequest::put()
+            .uri("/forex_pair")
+            .insert_header((header::IF_UNMODIFIED_SINCE, an_hour_ago.as_str()))
+            .set_json(json!({ "id": 1, "pair": "EUR/USD", "price": 1.5 }))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+        let req = test::TestRequest::patch()
+            .uri("/forex_pair/1")
+            .insert_header((header::IF_UNMODIFIED_SINCE, an_hour_ago.as_str()))
+            .set_json(json!({ "price": 1.5 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "precondition_failed");
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/2")
+            .insert_header((header::IF_UNMODIFIED_SINCE, an_hour_ago.as_str()))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::PRECONDITION_FAILED
+        );
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let stored: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(stored["price"], 1.08);
+
+        // Satisfied: Last-Modified sent back as is, or any later time
+        let req = test::TestRequest::patch()
+            .uri("/forex_pair/1")
+            .insert_header((header::IF_UNMODIFIED_SINCE, last_modified.as_str()))
+            .set_json(json!({ "price": 1.09 }))
+            .to_request();
+        let patched: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(patched["price"], 1.09);
+        let in_an_hour: String = (Utc::now() + chrono::Duration::hours(1)).to_rfc3339();
+        let req = test::TestRequest::put()
+            .uri("/forex_pair")
+            .insert_header((header::IF_UNMODIFIED_SINCE, in_an_hour.as_str()))
+            .set_json(json!({ "id": 1, "pair": "EUR/USD", "price": 1.1 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/2")
+            .insert_header((header::IF_UNMODIFIED_SINCE, in_an_hour.as_str()))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/1")
+            .insert_header((header::IF_UNMODIFIED_SINCE, "yesterday"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_web::test]
+    async fn tests_oldest_lists_longest_standing_pairs() {
+        let now: DateTime<Utc> = Utc::now();
+        let mut db: Database = temp_database("oldest");
+        for (id, pair, hours_ago) in [(1, "EUR/USD", 2), (2, "GBP/USD", 48), (3, "USD/JPY", 5)] {
+            let mut record: ForexPair = forex_pair(id, pair, 1.0);
+            record.created_at = Some(now - chrono::Duration::hours(hours_ago));
+            db.insert(record);
+        }
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/oldest?n=2")
+            .to_request();
+        let oldest: Value = test::call_and_read_body_json(&app, req).await;
+        let ids: Vec<u64> = oldest
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|forex_pair| forex_pair["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, vec![2, 3]);
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/oldest?n=1000")
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_web::test]
+    async fn tests_checksum_changes_with_the_data() {
+        let mut db: Database = temp_database("checksum");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let app = init_app(test_state(db, Config::default())).await;
+        let checksum = || {
+            test::TestRequest::get()
+                .uri("/forex_pairs/checksum")
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, checksum()).await;
+        let etag: String = resp
+            .headers()
+            .get(header::ETAG)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let original: Value = test::read_body_json(resp).await;
+        assert_eq!(
+            etag,
+            format!("\"{}\"", original["checksum"].as_str().unwrap())
+        );
+        assert_eq!(original["pair_count"], 1);
+
+        // Unchanged: 304 for a mirror that has it
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/checksum")
+            .insert_header((header::IF_NONE_MATCH, etag.as_str()))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::NOT_MODIFIED
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "id": 2, "pair": "GBP/USD", "price": 1.27 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let inserted: Value = test::call_and_read_body_json(&app, checksum()).await;
+        assert_ne!(inserted["checksum"], original["checksum"]);
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/checksum")
+            .insert_header((header::IF_NONE_MATCH, etag.as_str()))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/2")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let restored: Value = test::call_and_read_body_json(&app, checksum()).await;
+        assert_eq!(restored["checksum"], original["checksum"]);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::breaker::BreakerState;
+use crate::database::Database;
+use crate::paths::data_dir;
+use crate::provider::PriceProvider;
+use crate::state::AppState;
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Priced by the provider ping, any pair the provider knows would do
+const PROBE_PAIR: &str = "EUR/USD";
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(5);
+// Keeps the probe file of each deep check apart from those of concurrent ones
+static PROBE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Deserialize, Debug)]
+pub struct HealthQuery {
+    #[serde(default)]
+    pub deep: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CheckResult {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HealthReport {
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistence: Option<CheckResult>,
+    // Only checked when a price provider is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<CheckResult>,
+}
+
+impl CheckResult {
+    fn from_result<E: std::fmt::Display>(result: Result<(), E>) -> Self {
+        match result {
+            Ok(()) => CheckResult {
+                ok: true,
+                error: None,
+            },
+            Err(e) => CheckResult {
+                ok: false,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+// Cheap liveness check by default, ?deep=true also proves the data dir round-trips and the
+// price provider answers. 503 when either fails, the report says which.
+pub async fn health(
+    query: web::Query<HealthQuery>,
+    app_state: web::Data<AppState>,
+) -> impl Responder {
+    if !query.deep {
+        return HttpResponse::Ok().json(HealthReport {
+            status: "ok",
+            persistence: None,
+            provider: None,
+        });
+    }
+
+    let database_path: PathBuf = {
+        let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        db.database_path().to_path_buf()
+    };
+    // The fs calls would otherwise hold up this worker's other requests
+    let persistence: CheckResult = match web::block(move || probe_data_dir(&database_path)).await {
+        Ok(result) => CheckResult::from_result(result),
+        Err(e) => CheckResult::from_result(Err(e)),
+    };
+    let provider: Option<CheckResult> = match &app_state.provider {
+        Some(provider) => Some(CheckResult::from_result(
+            ping_provider(&app_state, provider.as_ref()).await,
+        )),
+        None => None,
+    };
+
+    let healthy: bool = persistence.ok && provider.as_ref().is_none_or(|provider| provider.ok);
+    let report: HealthReport = HealthReport {
+        status: if healthy { "ok" } else { "unavailable" },
+        persistence: Some(persistence),
+        provider,
+    };
+    if healthy {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+// One quote for PROBE_PAIR. An open circuit breaker counts as down without calling out; the
+// outcome is not fed back to the breaker, refresh traffic decides its state.
+async fn ping_provider(app_state: &AppState, provider: &dyn PriceProvider) -> Result<(), String> {
+    if app_state.provider_breaker.status().state == BreakerState::Open {
+        return Err("circuit breaker is open".to_string());
+    }
+    match tokio::time::timeout(PROVIDER_TIMEOUT, provider.fetch_price(PROBE_PAIR)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+// Write a small probe next to the database file, read it back and clean up
+fn probe_data_dir(database_path: &Path) -> std::io::Result<()> {
+    let probe_path: PathBuf = data_dir(database_path).join(format!(
+        ".health_probe_{}_{}",
+        std::process::id(),
+        PROBE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let token: String = format!(
+        "{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+    );
+
+    fs::write(&probe_path, &token)?;
+    let read_back: std::io::Result<String> = fs::read_to_string(&probe_path);
+    let _ = fs::remove_file(&probe_path);
+
+    if read_back? != token {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "probe file contents did not round-trip",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::provider::{PriceProvider, ProviderError};
+    use crate::state::AppState;
+    use crate::test_support::{init_app, temp_database, temp_dir, test_state};
+    use actix_web::{http::StatusCode, test, web};
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    struct DownProvider;
+
+    #[async_trait]
+    impl PriceProvider for DownProvider {
+        async fn fetch_price(&self, _pair: &str) -> Result<f64, ProviderError> {
+            Err(ProviderError::Unavailable("connection refused".into()))
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_health_is_cheap_by_default() {
+        let app = init_app(test_state(temp_database("health"), Config::default())).await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["status"], "ok");
+        assert!(body.get("persistence").is_none());
+    }
+
+    #[actix_web::test]
+    async fn tests_deep_health_checks_persistence() {
+        let app = init_app(test_state(temp_database("health_deep"), Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/health?deep=true")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["persistence"]["ok"], true);
+        assert!(body.get("provider").is_none());
+    }
+
+    #[actix_web::test]
+    async fn tests_deep_health_fails_when_provider_is_down() {
+        let state: web::Data<AppState> = web::Data::new(
+            AppState::new(temp_database("health_provider"), Config::default())
+                .with_provider(Arc::new(DownProvider)),
+        );
+        let app = init_app(state).await;
+
+        let req = test::TestRequest::get()
+            .uri("/health?deep=true")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["persistence"]["ok"], true);
+        assert_eq!(body["provider"]["ok"], false);
+        assert!(body["provider"]["error"].is_string());
+
+        // The cheap check does not call out
+        let req = test::TestRequest::get().uri("/health").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn tests_deep_health_fails_on_unwritable_data_dir() {
+        // A data dir that does not exist cannot be written to, even as root
+        let db: Database = Database::with_path(
+            temp_dir("health_unwritable")
+                .join("missing")
+                .join("database.json"),
+        );
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/health?deep=true")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["persistence"]["ok"], false);
+        assert!(body["persistence"]["error"].is_string());
+    }
+}
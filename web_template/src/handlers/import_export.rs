@@ -0,0 +1,638 @@
+use crate::auth::Admin;
+use crate::database::{Database, ForexPair, MergeSummary};
+use crate::error::AppError;
+use crate::persistence::Snapshot;
+use crate::state::{AppState, WritePermit};
+use actix_web::http::header::{self, ContentType};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Database blobs can be far larger than actix's default 256kB payload limit
+pub const IMPORT_PAYLOAD_LIMIT: usize = 16 * 1024 * 1024;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    #[default]
+    Merge,
+    Replace,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub mode: ImportMode,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportSummary {
+    pub mode: ImportMode,
+    pub received: usize,
+    pub inserted: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub total: usize,
+}
+
+pub const DUMP_VERSION: u32 = 1;
+
+// Lossless snapshot for moving data between instances, ids are kept as-is
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DatabaseDump {
+    pub dump_version: u32,
+    pub database: Database,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RestoreQuery {
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RestoreReport {
+    pub restored: usize,
+    pub overwritten: usize,
+    pub collisions: Vec<u64>,
+}
+
+// Export the live records in the same format as database.json. Unauthenticated, so per-user
+// and admin data stays out; /admin/dump has everything.
+pub async fn export_json(app_state: web::Data<AppState>) -> impl Responder {
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    HttpResponse::Ok().json(db.export())
+}
+
+// One row per pair ordered by id; stable ordering is what makes Range resumption safe
+pub async fn export_csv(req: HttpRequest, app_state: web::Data<AppState>) -> HttpResponse {
+    let mut body: String = String::from("id,pair,price,bid,ask\n");
+    for forex_pair in sorted_pairs(&app_state) {
+        let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+        body.push_str(&format!(
+            "{},{},{},{},{}\n",
+            forex_pair.id,
+            csv_field(&forex_pair.pair),
+            forex_pair.price,
+            optional(forex_pair.bid),
+            optional(forex_pair.ask)
+        ));
+    }
+    ranged(&req, "text/csv; charset=utf-8", body.into_bytes())
+}
+
+// One JSON object per line, same shape as GET /forex_pair/{id}
+pub async fn export_ndjson(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let mut body: Vec<u8> = Vec::new();
+    for forex_pair in sorted_pairs(&app_state) {
+        serde_json::to_writer(&mut body, &forex_pair)
+            .map_err(|e| AppError::Internal(format!("failed to encode export: {}", e)))?;
+        body.push(b'\n');
+    }
+    Ok(ranged(&req, "application/x-ndjson", body))
+}
+
+fn sorted_pairs(app_state: &AppState) -> Vec<ForexPair> {
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    // Already in id order
+    db.iter().cloned().collect()
+}
+
+// Quote a field when it holds a separator, quote or newline (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Serve body whole (200, advertising Accept-Ranges) or the single byte range asked for (206).
+// Multi-range requests get the whole body, which RFC 9110 allows.
+fn ranged(req: &HttpRequest, content_type: &str, body: Vec<u8>) -> HttpResponse {
+    let len: usize = body.len();
+    let range: Option<&str> = req
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+
+    match range.map(|range| parse_byte_range(range, len)) {
+        Some(Ok(Some((start, end)))) => HttpResponse::PartialContent()
+            .content_type(ContentType(content_type.parse().unwrap()))
+            .insert_header((header::ACCEPT_RANGES, "bytes"))
+            .insert_header((
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, len),
+            ))
+            .body(body[start..=end].to_vec()),
+        Some(Err(())) => HttpResponse::RangeNotSatisfiable()
+            .insert_header((header::CONTENT_RANGE, format!("bytes */{}", len)))
+            .finish(),
+        _ => HttpResponse::Ok()
+            .content_type(ContentType(content_type.parse().unwrap()))
+            .insert_header((header::ACCEPT_RANGES, "bytes"))
+            .body(body),
+    }
+}
+
+// `bytes=a-b`, `bytes=a-` or `bytes=-n` into an inclusive range. Ok(None) means serve the
+// whole body (not a byte range, or several ranges); Err means nothing in the body matches.
+fn parse_byte_range(header: &str, len: usize) -> Result<Option<(usize, usize)>, ()> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+    let (start, end): (usize, usize) = match (start.trim(), end.trim()) {
+        ("", "") => return Ok(None),
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().map_err(|_| ())?;
+            if suffix == 0 || len == 0 {
+                return Err(());
+            }
+            (len.saturating_sub(suffix), len - 1)
+        }
+        (start, end) => {
+            let start: usize = start.parse().map_err(|_| ())?;
+            let end: usize = match end {
+                "" => usize::MAX,
+                end => end.parse().map_err(|_| ())?,
+            };
+            if start >= len || end < start {
+                return Err(());
+            }
+            (start, end.min(len - 1))
+        }
+    };
+    Ok(Some((start, end)))
+}
+
+pub const BY_PRICE_FILE: &str = "database_by_price.json";
+
+#[derive(Serialize, Debug)]
+pub struct SortedExportReport {
+    pub output_file: String,
+    pub pair_count: usize,
+}
+
+// Write a copy of the pairs to database_by_price.json next to the database file, keyed by
+// rank in ascending price order (ties by id). The live database is not touched.
+pub async fn sort_by_price(
+    _admin: Admin,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let (mut forex_pairs, output_path): (Vec<ForexPair>, PathBuf) = {
+        let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let data_dir: &Path = db.database_path().parent().unwrap_or(Path::new(""));
+        (db.iter().cloned().collect(), data_dir.join(BY_PRICE_FILE))
+    };
+    forex_pairs.sort();
+    let by_price: BTreeMap<u64, ForexPair> = (0..).zip(forex_pairs).collect();
+
+    let contents: Vec<u8> = serde_json::to_vec_pretty(&by_price)
+        .map_err(|e| AppError::Internal(format!("failed to serialise {}: {}", BY_PRICE_FILE, e)))?;
+    app_state
+        .write_data_file(output_path, contents)
+        .await
+        .map_err(|e| AppError::Internal(format!("failed to write {}: {}", BY_PRICE_FILE, e)))?;
+    Ok(HttpResponse::Ok().json(SortedExportReport {
+        output_file: BY_PRICE_FILE.to_string(),
+        pair_count: by_price.len(),
+    }))
+}
+
+// Import a database.json blob, merging into or replacing the live database
+pub async fn import_json(
+    _admin: Admin,
+    req: HttpRequest,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    if req.content_type() != "application/json" {
+        return Err(AppError::UnsupportedMediaType(
+            "expected Content-Type: application/json".to_string(),
+        ));
+    }
+
+    let incoming: Database = Database::from_reader(&body[..])
+        .map_err(|e| AppError::BadRequest(format!("invalid database blob: {}", e)))?;
+    incoming
+        .check_integrity()
+        .map_err(|problems| AppError::BadRequest(problems.join("; ")))?;
+
+    let received: usize = incoming.len();
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    // Scoped so the data lock is released before the file is written
+    let (merged, removed, total, snapshot): (MergeSummary, usize, usize, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let previous: usize = db.len();
+
+        let (merged, removed): (MergeSummary, usize) = match query.mode {
+            ImportMode::Merge => (
+                db.merge(incoming)
+                    .map_err(|problems| AppError::Conflict(problems.join("; ")))?,
+                0,
+            ),
+            ImportMode::Replace => {
+                let kept: usize = incoming
+                    .iter()
+                    .filter(|forex_pair| db.get(&forex_pair.id).is_some())
+                    .count();
+                db.replace(incoming);
+                let summary: MergeSummary = MergeSummary {
+                    inserted: received - kept,
+                    updated: kept,
+                };
+                (summary, previous - kept)
+            }
+        };
+        (merged, removed, db.len(), app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+
+    Ok(HttpResponse::Ok().json(ImportSummary {
+        mode: query.mode,
+        received,
+        inserted: merged.inserted,
+        updated: merged.updated,
+        removed,
+        total,
+    }))
+}
+
+// Dump the whole database, ids included, for /admin/restore on another instance
+pub async fn dump(_admin: Admin, app_state: web::Data<AppState>) -> impl Responder {
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    HttpResponse::Ok().json(DatabaseDump {
+        dump_version: DUMP_VERSION,
+        database: db.clone(),
+    })
+}
+
+// Restore a dump keeping its ids, timestamps and history. Refuses with 409 on id collisions
+// unless ?overwrite=true, and always when a pair is already stored under another id
+pub async fn restore(
+    _admin: Admin,
+    query: web::Query<RestoreQuery>,
+    body: web::Bytes,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let dump: DatabaseDump = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("invalid dump: {}", e)))?;
+    if dump.dump_version != DUMP_VERSION {
+        return Err(AppError::BadRequest(format!(
+            "unsupported dump_version {}, expected {}",
+            dump.dump_version, DUMP_VERSION
+        )));
+    }
+    dump.database
+        .check_integrity()
+        .map_err(|problems| AppError::BadRequest(problems.join("; ")))?;
+
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (collisions, summary, snapshot): (Vec<u64>, MergeSummary, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let collisions: Vec<u64> = db.colliding_ids(&dump.database);
+        if !collisions.is_empty() && !query.overwrite {
+            return Ok(HttpResponse::Conflict().json(RestoreReport {
+                restored: 0,
+                overwritten: 0,
+                collisions,
+            }));
+        }
+        let summary: MergeSummary = db
+            .restore(dump.database)
+            .map_err(|problems| AppError::Conflict(problems.join("; ")))?;
+        (collisions, summary, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+
+    Ok(HttpResponse::Ok().json(RestoreReport {
+        restored: summary.inserted + summary.updated,
+        overwritten: summary.updated,
+        collisions,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_byte_range;
+    use crate::database::Database;
+    use crate::test_support::{
+        admin_config, admin_header, forex_pair, init_app, temp_database, temp_dir, test_state,
+    };
+    use actix_web::{http::header, http::StatusCode, test};
+    use serde_json::{json, Value};
+
+    fn seeded_database() -> Database {
+        let mut db: Database = temp_database("import_json");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        db
+    }
+
+    #[actix_web::test]
+    async fn tests_import_json_round_trips_modified_export() {
+        let mut db: Database = seeded_database();
+        db.add_to_watchlist("alice", "EUR/USD");
+        let path = db.database_path().to_path_buf();
+        let app = init_app(test_state(db, admin_config())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/export/json")
+            .to_request();
+        let mut export: Value = test::call_and_read_body_json(&app, req).await;
+        let mut keys: Vec<&String> = export.as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["forex_pairs", "next_id"]);
+
+        // Change a price, drop a pair and add a new one
+        export["forex_pairs"]["1"]["price"] = json!(1.1);
+        export["forex_pairs"].as_object_mut().unwrap().remove("2");
+        export["forex_pairs"]["3"] = json!({ "id": 3, "pair": "USD/JPY", "price": 151.2 });
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/import/json?mode=replace")
+            .insert_header(admin_header())
+            .set_json(&export)
+            .to_request();
+        let summary: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(summary["inserted"], 1);
+        assert_eq!(summary["updated"], 1);
+        assert_eq!(summary["removed"], 1);
+        assert_eq!(summary["total"], 2);
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/export/json")
+            .to_request();
+        let live: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(live["forex_pairs"], export["forex_pairs"]);
+
+        // And the import was persisted
+        let saved: Database = Database::load_from_file(&path).unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved.get(&1).unwrap().price, 1.1);
+        assert!(saved.get(&2).is_none());
+    }
+
+    #[actix_web::test]
+    async fn tests_import_json_merges_by_default() {
+        let app = init_app(test_state(seeded_database(), admin_config())).await;
+
+        let blob: Value = json!({
+            "forex_pairs": { "2": { "id": 2, "pair": "GBP/USD", "price": 1.3 } }
+        });
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/import/json")
+            .insert_header(admin_header())
+            .set_json(&blob)
+            .to_request();
+        let summary: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(summary["mode"], "merge");
+        assert_eq!(summary["updated"], 1);
+        assert_eq!(summary["total"], 2);
+
+        // EUR/USD is id 1 already, a second copy under id 5 is refused
+        let blob: Value = json!({
+            "forex_pairs": { "5": { "id": 5, "pair": "EUR/USD", "price": 1.1 } }
+        });
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/import/json")
+            .insert_header(admin_header())
+            .set_json(&blob)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn tests_import_json_rejects_bad_input() {
+        let app = init_app(test_state(seeded_database(), admin_config())).await;
+
+        // Key does not match the record id
+        let blob: Value = json!({
+            "forex_pairs": { "9": { "id": 2, "pair": "GBP/USD", "price": 1.3 } }
+        });
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/import/json")
+            .insert_header(admin_header())
+            .set_json(&blob)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/import/json")
+            .insert_header(admin_header())
+            .insert_header((header::CONTENT_TYPE, "text/plain"))
+            .set_payload(blob.to_string())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/import/json")
+            .set_json(&blob)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn tests_dump_and_restore_preserve_ids() {
+        let mut source: Database = temp_database("dump_source");
+        source.insert(forex_pair(5, "EUR/USD", 1.08));
+        source.insert(forex_pair(9, "GBP/USD", 1.27));
+        source.insert(forex_pair(42, "USD/JPY", 151.2));
+        let source_app = init_app(test_state(source, admin_config())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/dump")
+            .insert_header(admin_header())
+            .to_request();
+        let dump: Value = test::call_and_read_body_json(&source_app, req).await;
+
+        let target_app = init_app(test_state(temp_database("dump_target"), admin_config())).await;
+        let req = test::TestRequest::post()
+            .uri("/admin/restore")
+            .insert_header(admin_header())
+            .set_json(&dump)
+            .to_request();
+        let report: Value = test::call_and_read_body_json(&target_app, req).await;
+        assert_eq!(report["restored"], 3);
+        assert_eq!(report["collisions"], json!([]));
+
+        for (id, pair) in [(5, "EUR/USD"), (9, "GBP/USD"), (42, "USD/JPY")] {
+            let req = test::TestRequest::get()
+                .uri(&format!("/forex_pair/{}", id))
+                .to_request();
+            let restored: Value = test::call_and_read_body_json(&target_app, req).await;
+            assert_eq!(restored["id"], id);
+            assert_eq!(restored["pair"], pair);
+        }
+
+        // Restoring the same dump again collides on every id
+        let req = test::TestRequest::post()
+            .uri("/admin/restore")
+            .insert_header(admin_header())
+            .set_json(&dump)
+            .to_request();
+        let resp = test::call_service(&target_app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let report: Value = test::read_body_json(resp).await;
+        assert_eq!(report["collisions"], json!([5, 9, 42]));
+
+        let req = test::TestRequest::post()
+            .uri("/admin/restore?overwrite=true")
+            .insert_header(admin_header())
+            .set_json(&dump)
+            .to_request();
+        let report: Value = test::call_and_read_body_json(&target_app, req).await;
+        assert_eq!(report["overwritten"], 3);
+
+        // A pair the target already holds under another id is refused
+        let other_app = init_app(test_state(seeded_database(), admin_config())).await;
+        let req = test::TestRequest::post()
+            .uri("/admin/restore")
+            .insert_header(admin_header())
+            .set_json(&dump)
+            .to_request();
+        assert_eq!(
+            test::call_service(&other_app, req).await.status(),
+            StatusCode::CONFLICT
+        );
+    }
+
+    // `test` here is actix_web::test, hence the async unit test
+    #[actix_web::test]
+    async fn tests_parse_byte_range() {
+        assert_eq!(parse_byte_range("bytes=0-9", 100), Ok(Some((0, 9))));
+        assert_eq!(parse_byte_range("bytes=90-", 100), Ok(Some((90, 99))));
+        assert_eq!(parse_byte_range("bytes=-10", 100), Ok(Some((90, 99))));
+        assert_eq!(parse_byte_range("bytes=50-500", 100), Ok(Some((50, 99))));
+        assert_eq!(parse_byte_range("bytes=0-1,5-6", 100), Ok(None));
+        assert_eq!(parse_byte_range("items=0-1", 100), Ok(None));
+        assert_eq!(parse_byte_range("bytes=100-", 100), Err(()));
+        assert_eq!(parse_byte_range("bytes=9-1", 100), Err(()));
+    }
+
+    #[actix_web::test]
+    async fn tests_export_range_requests_return_matching_slice() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(3, "USD/JPY", 151.2));
+        let app = init_app(test_state(db, admin_config())).await;
+
+        for uri in ["/forex_pairs/export/csv", "/forex_pairs/export/ndjson"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers().get(header::ACCEPT_RANGES).unwrap(), "bytes");
+            let full = test::read_body(resp).await;
+
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .insert_header((header::RANGE, "bytes=10-29"))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+            assert_eq!(
+                resp.headers()
+                    .get(header::CONTENT_RANGE)
+                    .unwrap()
+                    .to_str()
+                    .unwrap(),
+                format!("bytes 10-29/{}", full.len())
+            );
+            let slice = test::read_body(resp).await;
+            assert_eq!(slice, full.slice(10..30));
+
+            let req = test::TestRequest::get()
+                .uri(uri)
+                .insert_header((header::RANGE, format!("bytes={}-", full.len())))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/export/csv")
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let csv: &str = std::str::from_utf8(&body).unwrap();
+        assert_eq!(
+            csv,
+            "id,pair,price,bid,ask\n1,EUR/USD,1.08,,\n2,GBP/USD,1.27,,\n3,USD/JPY,151.2,,\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn tests_sort_by_price_writes_ascending_copy() {
+        let mut db: Database = temp_database("sort_by_price");
+        db.insert(forex_pair(1, "USD/JPY", 151.2));
+        db.insert(forex_pair(2, "EUR/USD", 1.08));
+        db.insert(forex_pair(3, "AUD/USD", 0.66));
+        db.insert(forex_pair(4, "GBP/USD", 1.27));
+        let dir = db.database_path().parent().unwrap().to_path_buf();
+        let app = init_app(test_state(db, admin_config())).await;
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/sort_by_price")
+            .insert_header(admin_header())
+            .to_request();
+        let report: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            report,
+            json!({ "output_file": "database_by_price.json", "pair_count": 4 })
+        );
+
+        let written: String = std::fs::read_to_string(dir.join("database_by_price.json")).unwrap();
+        let written: serde_json::Map<String, Value> = serde_json::from_str(&written).unwrap();
+        let prices: Vec<f64> = written
+            .values()
+            .map(|forex_pair| forex_pair["price"].as_f64().unwrap())
+            .collect();
+        assert_eq!(prices.len(), 4);
+        assert!(
+            prices.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            prices
+        );
+
+        // The live database keeps its ids
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let live: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(live["pair"], "USD/JPY");
+    }
+
+    #[actix_web::test]
+    async fn tests_sort_by_price_reports_failed_write() {
+        let mut db: Database = Database::with_path(
+            temp_dir("sort_by_price_missing")
+                .join("missing")
+                .join("database.json"),
+        );
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let app = init_app(test_state(db, admin_config())).await;
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pairs/sort_by_price")
+            .insert_header(admin_header())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body: Value = test::read_body_json(resp).await;
+        let message: &str = body["message"].as_str().unwrap();
+        assert!(message.contains("database_by_price.json"), "{}", message);
+    }
+}
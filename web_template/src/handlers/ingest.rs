@@ -0,0 +1,220 @@
+use crate::database::{Database, ForexPair};
+use crate::error::AppError;
+use crate::persistence::Snapshot;
+use crate::state::{AppState, WritePermit};
+use actix_web::{web, HttpResponse};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::{MutexGuard, TryLockError};
+use std::time::Duration;
+
+// Longest line accepted; anything longer is refused rather than buffered without bound
+pub const MAX_INGEST_LINE_BYTES: usize = 64 * 1024;
+
+// How long to back off before retrying a contended data lock
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(1);
+
+#[derive(Deserialize, Debug)]
+struct IngestLine {
+    pair: String,
+    #[serde(deserialize_with = "crate::database::deserialize_price")]
+    price: f64,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct IngestSummary {
+    pub lines: usize,
+    pub applied: usize,
+    // Well-formed lines for a pair that is not stored
+    pub unknown: usize,
+    // Lines that are not valid JSON, name an invalid pair or fail price validation
+    pub rejected: usize,
+}
+
+// Splits a byte stream into lines, holding on to at most one partial line between chunks
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    partial: Vec<u8>,
+}
+
+impl LineBuffer {
+    // Complete lines in this chunk, in order; a trailing fragment waits for the next chunk
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<Vec<u8>>, AppError> {
+        let mut lines: Vec<Vec<u8>> = Vec::new();
+        let mut rest: &[u8] = chunk;
+        while let Some(end) = rest.iter().position(|byte| *byte == b'\n') {
+            self.partial.extend_from_slice(&rest[..end]);
+            lines.push(std::mem::take(&mut self.partial));
+            rest = &rest[end + 1..];
+        }
+        self.partial.extend_from_slice(rest);
+        if self.partial.len() > MAX_INGEST_LINE_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "ingest lines must be at most {} bytes",
+                MAX_INGEST_LINE_BYTES
+            )));
+        }
+        Ok(lines)
+    }
+
+    // Whatever is left once the stream ends (a last line without a newline)
+    pub fn finish(self) -> Option<Vec<u8>> {
+        Some(self.partial).filter(|partial| !partial.is_empty())
+    }
+}
+
+// Stream of `{"pair": "EUR/USD", "price": 1.0842}` lines, applied chunk by chunk as they
+// arrive. Each line updates a stored pair's price and appends to its history. The next chunk
+// is only read once the previous one is applied, so a slow or contended data lock pushes
+// back on the sender. The file is saved once, at the end; no per-line events are published.
+pub async fn ingest(
+    app_state: web::Data<AppState>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, AppError> {
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let mut summary: IngestSummary = IngestSummary::default();
+    let mut buffer: LineBuffer = LineBuffer::default();
+
+    while let Some(chunk) = payload.next().await {
+        let chunk: web::Bytes =
+            chunk.map_err(|e| AppError::BadRequest(format!("failed to read body: {}", e)))?;
+        let lines: Vec<Vec<u8>> = buffer.feed(&chunk)?;
+        let mut db: MutexGuard<Database> = lock_data(&app_state).await;
+        for line in lines {
+            apply_line(&mut db, &app_state, &line, &mut summary);
+        }
+    }
+    if let Some(line) = buffer.finish() {
+        let mut db: MutexGuard<Database> = lock_data(&app_state).await;
+        apply_line(&mut db, &app_state, &line, &mut summary);
+    }
+
+    if summary.applied > 0 {
+        let snapshot: Snapshot = {
+            let db: MutexGuard<Database> = lock_data(&app_state).await;
+            app_state.snapshot(&db)?
+        };
+        app_state.persist(snapshot).await?;
+    }
+    tracing::info!(
+        lines = summary.lines,
+        applied = summary.applied,
+        "ingest finished"
+    );
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+// Wait for the data lock without blocking the worker thread while others hold it
+async fn lock_data(app_state: &AppState) -> MutexGuard<'_, Database> {
+    loop {
+        match app_state.db.try_lock() {
+            Ok(db) => return db,
+            Err(TryLockError::Poisoned(poisoned)) => return poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => tokio::time::sleep(LOCK_RETRY_DELAY).await,
+        }
+    }
+}
+
+fn apply_line(db: &mut Database, app_state: &AppState, line: &[u8], summary: &mut IngestSummary) {
+    let line: &[u8] = line.strip_suffix(b"\r").unwrap_or(line);
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return;
+    }
+    summary.lines += 1;
+
+    let Ok(update) = serde_json::from_slice::<IngestLine>(line) else {
+        summary.rejected += 1;
+        return;
+    };
+    let Ok(pair) = ForexPair::normalize_pair(&update.pair) else {
+        summary.rejected += 1;
+        return;
+    };
+    let Some(current) = db.find_by_pair(&pair) else {
+        summary.unknown += 1;
+        return;
+    };
+    let mut updated: ForexPair = current.clone();
+    updated.price = update.price;
+    if updated.validate(app_state.config.max_spread_pct).is_err() {
+        summary.rejected += 1;
+        return;
+    }
+    // No events from ingest, but the per-pair price change counter still applies
+    if updated.price != current.price {
+        app_state.metrics.record_price_change((&updated).into());
+    }
+    db.update(updated);
+    summary.applied += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{forex_pair, init_app, temp_database, test_state};
+    use actix_web::test;
+    use serde_json::Value;
+
+    // `test` here is actix_web::test, hence the async unit test
+    #[actix_web::test]
+    async fn tests_line_buffer_joins_lines_split_across_chunks() {
+        let mut buffer: LineBuffer = LineBuffer::default();
+        assert_eq!(buffer.feed(b"{\"a\"").unwrap(), Vec::<Vec<u8>>::new());
+        assert_eq!(
+            buffer.feed(b":1}\n{\"b\":2}\n{\"c\"").unwrap(),
+            vec![b"{\"a\":1}".to_vec(), b"{\"b\":2}".to_vec()]
+        );
+        assert_eq!(buffer.finish(), Some(b"{\"c\"".to_vec()));
+
+        let mut buffer: LineBuffer = LineBuffer::default();
+        assert!(buffer.feed(&vec![b'x'; MAX_INGEST_LINE_BYTES + 1]).is_err());
+    }
+
+    #[actix_web::test]
+    async fn tests_ingest_applies_every_line() {
+        let mut db: Database = temp_database("ingest");
+        db.insert(forex_pair(1, "EUR/USD", 1.0));
+        db.insert(forex_pair(2, "GBP/USD", 1.2));
+        let path = db.database_path().to_path_buf();
+        let state = test_state(db, Config::default());
+        let app = init_app(state.clone()).await;
+
+        let mut body: String = String::new();
+        for step in 1..=5000 {
+            let pair: &str = if step % 2 == 0 { "eur/usd" } else { "GBP-USD" };
+            body.push_str(&format!(
+                "{{\"pair\":\"{}\",\"price\":{}}}\n",
+                pair,
+                1.0 + step as f64 / 10_000.0
+            ));
+        }
+        body.push_str("{\"pair\":\"USD/JPY\",\"price\":151.2}\n");
+        body.push_str("not json\r\n\n");
+        body.push_str("{\"pair\":\"EUR/USD\",\"price\":-1}\n");
+        // No trailing newline on the last line
+        body.push_str("{\"pair\":\"EUR/USD\",\"price\":\"1.2345\"}");
+
+        let req = test::TestRequest::post()
+            .uri("/ingest")
+            .insert_header(("content-type", "application/x-ndjson"))
+            .set_payload(body)
+            .to_request();
+        let summary: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(summary["lines"], 5004);
+        assert_eq!(summary["applied"], 5001);
+        assert_eq!(summary["unknown"], 1);
+        assert_eq!(summary["rejected"], 2);
+
+        {
+            let db = state.db.lock().unwrap();
+            assert_eq!(db.get(&1).unwrap().price, 1.2345);
+            assert_eq!(db.get(&2).unwrap().price, 1.4999);
+            // The starting price plus one point per update
+            assert_eq!(db.price_history(&1).len(), 2502);
+            assert_eq!(db.price_history(&2).len(), 2501);
+        }
+        let saved: Database = Database::load_from_file(&path).unwrap();
+        assert_eq!(saved.get(&1).unwrap().price, 1.2345);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::state::AppState;
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct MetricsQuery {
+    // "json" for AppState::metrics() as JSON, anything else gets the Prometheus text
+    pub format: Option<String>,
+}
+
+// Prometheus scrape endpoint
+pub async fn metrics(
+    app_state: web::Data<AppState>,
+    query: web::Query<MetricsQuery>,
+) -> impl Responder {
+    if query.format.as_deref() == Some("json") {
+        return HttpResponse::Ok().json(app_state.metrics());
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(app_state.metrics.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::metrics::MetricsSnapshot;
+    use crate::state::{AppState, WritePermit};
+    use crate::test_support::{forex_pair, init_app, temp_database, test_state};
+    use actix_web::{http::StatusCode, test, web};
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn tests_full_write_backlog_returns_503() {
+        let config: Config = Config {
+            write_concurrency: 2,
+            ..Config::default()
+        };
+        let state: web::Data<AppState> = test_state(temp_database("write_semaphore"), config);
+        let app = init_app(state.clone()).await;
+        let create = |pair: &str| {
+            test::TestRequest::post()
+                .uri("/forex_pair")
+                .set_json(json!({ "pair": pair, "price": 1.0 }))
+                .to_request()
+        };
+
+        // Simulate two writes stuck in the critical section
+        let first: WritePermit = state.try_acquire_write().unwrap();
+        let second: WritePermit = state.try_acquire_write().unwrap();
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body: &str = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("\nwrite_queue_depth 2\n"));
+
+        let resp = test::call_service(&app, create("EUR/USD")).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        drop(first);
+        drop(second);
+        let resp = test::call_service(&app, create("EUR/USD")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(state.metrics.write_queue_depth(), 0);
+    }
+
+    #[actix_web::test]
+    async fn tests_metrics_snapshot_follows_requests_and_saves() {
+        let state: web::Data<AppState> = test_state(temp_database("metrics"), Config::default());
+        let app = init_app(state.clone()).await;
+        let before: MetricsSnapshot = state.metrics();
+        assert_eq!(before.request_count, 0);
+        assert_eq!(before.avg_latency_ms, 0.0);
+        assert!(!before.dirty);
+        assert_eq!(before.last_save, None);
+
+        let req = test::TestRequest::post()
+            .uri("/forex_pair")
+            .set_json(json!({ "pair": "EUR/USD", "price": 1.08 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        let req = test::TestRequest::get().uri("/forex_pair/9").to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::NOT_FOUND
+        );
+
+        let after: MetricsSnapshot = state.metrics();
+        assert_eq!(after.request_count, 2);
+        assert_eq!(after.error_count, 1);
+        assert!(after.avg_latency_ms >= 0.0);
+        assert_eq!(after.pair_count, 1);
+        assert!(!after.dirty);
+        assert!(after.last_save.is_some());
+
+        // A snapshot taken but not written yet leaves the state dirty
+        let _pending = {
+            let db = state.db.lock().unwrap();
+            state.snapshot(&db).unwrap()
+        };
+        assert!(state.metrics().dirty);
+    }
+
+    #[actix_web::test]
+    async fn tests_requests_land_in_their_route_histogram() {
+        let state: web::Data<AppState> =
+            test_state(temp_database("route_latency"), Config::default());
+        let app = init_app(state.clone()).await;
+        for id in [1, 2, 3] {
+            let req = test::TestRequest::get()
+                .uri(&format!("/forex_pair/{}", id))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/metrics?format=json")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        let route: &Value = body["routes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|route| route["route"] == "/forex_pair/{id}")
+            .unwrap();
+        assert_eq!(route["method"], "GET");
+        assert_eq!(route["count"], 3);
+        assert!(route["p99_ms"].as_f64().unwrap() >= route["p50_ms"].as_f64().unwrap());
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body: &str = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains(
+            "http_route_latency_ms_count{method=\"GET\",route=\"/forex_pair/{id}\"} 3\n"
+        ));
+        assert!(body.contains(
+            "http_route_latency_quantile_ms{method=\"GET\",route=\"/forex_pair/{id}\",quantile=\"0.95\"}"
+        ));
+    }
+
+    #[actix_web::test]
+    async fn tests_price_change_counter_is_labelled_with_the_pair() {
+        let mut db = temp_database("price_change_labels");
+        db.insert(forex_pair(4, "GBP/USD", 1.27));
+        let state: web::Data<AppState> = test_state(db, Config::default());
+        let app = init_app(state.clone()).await;
+        for price in [1.28, 1.28, 1.29] {
+            let req = test::TestRequest::put()
+                .uri("/forex_pair")
+                .set_json(json!({ "id": 4, "pair": "gbp/usd", "price": price }))
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        }
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body: &str = std::str::from_utf8(&body).unwrap();
+        // The second PUT left the price alone
+        assert!(body.contains("\nforex_price_changes_total{pair=\"GBP/USD\",id=\"4\"} 2\n"));
+        assert!(!body.contains("forex_price_alerts_total"));
+    }
+}
@@ -0,0 +1,14 @@
+pub mod admin;
+pub mod exposure;
+pub mod forex_pair;
+pub mod health;
+pub mod import_export;
+pub mod ingest;
+pub mod metrics;
+pub mod provider;
+pub mod rates;
+pub mod simulation;
+pub mod stats;
+pub mod validate;
+pub mod wait;
+pub mod watchlist;
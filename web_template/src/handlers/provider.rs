@@ -0,0 +1,328 @@
+use crate::auth::Actor;
+use crate::breaker::BreakerCall;
+use crate::database::{Database, ForexPair};
+use crate::error::AppError;
+use crate::events::ForexPairEvent;
+use crate::persistence::Snapshot;
+use crate::provider::{PriceProvider, ProviderError, ProviderQuote};
+use crate::state::{AppState, WritePermit};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Object keys whose values never leave the server, matched case-insensitively as substrings
+const SENSITIVE_KEYS: &[&str] = &["key", "token", "secret", "password", "auth", "signature"];
+const REDACTED: &str = "[redacted]";
+
+#[derive(Deserialize, Debug)]
+pub struct RefreshQuery {
+    #[serde(default)]
+    pub debug: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RefreshResponse {
+    #[serde(flatten)]
+    pub forex_pair: ForexPair,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_debug: Option<Value>,
+}
+
+// Pull the current price for {id} from the provider. The circuit breaker answers 503 with
+// Retry-After while the provider is failing or has asked us to back off. With ?debug=true,
+// PROVIDER_DEBUG on and an admin token, the redacted upstream body is included as well.
+pub async fn refresh_forex_pair(
+    actor: Actor,
+    app_state: web::Data<AppState>,
+    id: web::Path<u64>,
+    query: web::Query<RefreshQuery>,
+) -> Result<HttpResponse, AppError> {
+    let id: u64 = id.into_inner();
+    let provider: Arc<dyn PriceProvider> = app_state
+        .provider
+        .clone()
+        .ok_or_else(|| AppError::ServiceUnavailable("no price provider configured".to_string()))?;
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let pair: String = {
+        let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        db.get(&id)
+            .map(|forex_pair| forex_pair.pair.clone())
+            .ok_or_else(|| AppError::NotFound(format!("no forex pair with id {}", id)))?
+    };
+
+    let call: BreakerCall = app_state
+        .provider_breaker
+        .try_call()
+        .map_err(|wait| retry_later("price provider circuit is open", wait))?;
+    let quote: ProviderQuote = match provider.fetch_quote(&pair).await {
+        Ok(quote) => {
+            call.succeeded();
+            quote
+        }
+        Err(err) => {
+            call.failed(err.retry_after());
+            return Err(match &err {
+                ProviderError::RateLimited { retry_after } => retry_later(
+                    &err.to_string(),
+                    retry_after.unwrap_or(app_state.config.breaker_cooldown),
+                ),
+                _ => AppError::BadGateway(err.to_string()),
+            });
+        }
+    };
+
+    let (previous, refreshed, snapshot): (ForexPair, ForexPair, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        // Re-read: the record may have changed or gone while the provider was answering
+        let previous: ForexPair = db
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("no forex pair with id {}", id)))?;
+        let mut refreshed: ForexPair = previous.clone();
+        refreshed.price = quote.price;
+        refreshed.validate(app_state.config.max_spread_pct)?;
+        db.update(refreshed.clone());
+        (previous, refreshed, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    app_state.publish(ForexPairEvent::updated(&actor.0, &previous, &refreshed));
+
+    let show_debug: bool = query.debug && app_state.config.provider_debug && actor.0 == "admin";
+    Ok(HttpResponse::Ok().json(RefreshResponse {
+        forex_pair: refreshed,
+        provider_debug: show_debug.then(|| provider_debug(&quote)),
+    }))
+}
+
+fn provider_debug(quote: &ProviderQuote) -> Value {
+    let raw: Value = match &quote.raw {
+        Some(raw) => match serde_json::from_str::<Value>(raw) {
+            Ok(parsed) => redact(parsed),
+            // Not JSON, so there are no keys to go by; only say how much there was
+            Err(_) => json!(format!("[{} bytes of non-JSON body]", raw.len())),
+        },
+        None => Value::Null,
+    };
+    json!({ "raw": raw, "parsed": { "price": quote.price } })
+}
+
+// Replace the values of sensitive-looking keys at any depth
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| {
+                    let lower: String = key.to_ascii_lowercase();
+                    if SENSITIVE_KEYS.iter().any(|word| lower.contains(word)) {
+                        (key, json!(REDACTED))
+                    } else {
+                        (key, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        other => other,
+    }
+}
+
+fn retry_later(message: &str, wait: Duration) -> AppError {
+    AppError::RetryLater {
+        message: message.to_string(),
+        retry_after: wait.as_secs_f64().ceil().max(1.0) as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::provider::{PriceProvider, ProviderError, ProviderQuote};
+    use crate::state::AppState;
+    use crate::test_support::{admin_config, admin_header, forex_pair, init_app, temp_database};
+    use actix_web::{http::header, http::StatusCode, test, web};
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // Fails the first `failures` calls, then quotes 1.1
+    struct StubProvider {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl PriceProvider for StubProvider {
+        async fn fetch_price(&self, _pair: &str) -> Result<f64, ProviderError> {
+            let call: u32 = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if call <= self.failures {
+                Err(ProviderError::Unavailable("connection reset".to_string()))
+            } else {
+                Ok(1.1)
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_breaker_short_circuits_refresh_until_it_closes() {
+        let mut db: Database = temp_database("breaker");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let config: Config = Config {
+            breaker_failure_threshold: 2,
+            breaker_cooldown: Duration::from_millis(200),
+            ..admin_config()
+        };
+        let provider: Arc<StubProvider> = Arc::new(StubProvider {
+            failures: 2,
+            calls: AtomicU32::new(0),
+        });
+        let state: web::Data<AppState> =
+            web::Data::new(AppState::new(db, config).with_provider(provider.clone()));
+        let app = init_app(state).await;
+        let refresh = || {
+            test::TestRequest::post()
+                .uri("/forex_pair/1/refresh")
+                .to_request()
+        };
+
+        for _ in 0..2 {
+            let resp = test::call_service(&app, refresh()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+        }
+
+        // Open: the provider is not called at all
+        let resp = test::call_service(&app, refresh()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "1");
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/debug/state")
+            .insert_header(admin_header())
+            .to_request();
+        let debug: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(debug["circuit_breaker_state"], "open");
+
+        // After the cooldown the half-open trial succeeds and the breaker closes
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let refreshed: Value = test::call_and_read_body_json(&app, refresh()).await;
+        assert_eq!(refreshed["price"], 1.1);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 3);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/debug/state")
+            .insert_header(admin_header())
+            .to_request();
+        let debug: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(debug["circuit_breaker_state"], "closed");
+    }
+
+    // Never answers, like an upstream that accepted the connection and went quiet
+    struct HangingProvider;
+
+    #[async_trait]
+    impl PriceProvider for HangingProvider {
+        async fn fetch_price(&self, _pair: &str) -> Result<f64, ProviderError> {
+            std::future::pending().await
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_cancelled_refresh_frees_the_half_open_trial() {
+        let mut db: Database = temp_database("breaker_cancel");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let config: Config = Config {
+            breaker_failure_threshold: 1,
+            breaker_cooldown: Duration::from_millis(50),
+            ..Config::default()
+        };
+        let state: web::Data<AppState> =
+            web::Data::new(AppState::new(db, config).with_provider(Arc::new(HangingProvider)));
+        let app = init_app(state.clone()).await;
+
+        state.provider_breaker.try_call().unwrap().failed(None);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        // The client gives up while the half-open trial is waiting on the provider
+        let req = test::TestRequest::post()
+            .uri("/forex_pair/1/refresh")
+            .to_request();
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(50), test::call_service(&app, req)).await;
+        assert!(cancelled.is_err());
+
+        // The next caller gets the trial rather than being held back for good
+        assert!(state.provider_breaker.try_call().is_ok());
+    }
+
+    // Quotes 1.1 with a body that carries a credential the provider echoed back
+    struct VerboseProvider;
+
+    #[async_trait]
+    impl PriceProvider for VerboseProvider {
+        async fn fetch_price(&self, _pair: &str) -> Result<f64, ProviderError> {
+            Ok(1.1)
+        }
+
+        async fn fetch_quote(&self, _pair: &str) -> Result<ProviderQuote, ProviderError> {
+            Ok(ProviderQuote {
+                price: 1.1,
+                raw: Some(
+                    r#"{"price":1.1,"source":"ecb","meta":{"api_key":"s3cret","Auth-Token":"t"}}"#
+                        .to_string(),
+                ),
+            })
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_refresh_debug_shows_redacted_raw_body_only_when_enabled() {
+        for provider_debug in [false, true] {
+            let mut db: Database = temp_database("refresh_debug");
+            db.insert(forex_pair(1, "EUR/USD", 1.08));
+            let config: Config = Config {
+                provider_debug,
+                ..admin_config()
+            };
+            let state: web::Data<AppState> =
+                web::Data::new(AppState::new(db, config).with_provider(Arc::new(VerboseProvider)));
+            let app = init_app(state).await;
+
+            let req = test::TestRequest::post()
+                .uri("/forex_pair/1/refresh?debug=true")
+                .insert_header(admin_header())
+                .to_request();
+            let body: Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(body["price"], 1.1);
+            if !provider_debug {
+                assert!(body.get("provider_debug").is_none());
+                continue;
+            }
+            let debug: &Value = &body["provider_debug"];
+            assert_eq!(debug["parsed"]["price"], 1.1);
+            assert_eq!(debug["raw"]["source"], "ecb");
+            assert_eq!(debug["raw"]["meta"]["api_key"], "[redacted]");
+            assert_eq!(debug["raw"]["meta"]["Auth-Token"], "[redacted]");
+            assert!(!body.to_string().contains("s3cret"));
+
+            // Not for anonymous callers, nor without ?debug=true
+            for req in [
+                test::TestRequest::post()
+                    .uri("/forex_pair/1/refresh?debug=true")
+                    .to_request(),
+                test::TestRequest::post()
+                    .uri("/forex_pair/1/refresh")
+                    .insert_header(admin_header())
+                    .to_request(),
+            ] {
+                let body: Value = test::call_and_read_body_json(&app, req).await;
+                assert!(body.get("provider_debug").is_none());
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::converter::{convert_rate, normalize_currency, Rate, RateSource};
+use crate::database::Database;
+use crate::error::AppError;
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize, Debug)]
+pub struct RatesQuery {
+    pub base: String,
+    // Comma separated list, e.g. USD,GBP,JPY
+    pub quotes: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum QuoteResult {
+    Rate {
+        rate: f64,
+        derived: bool,
+        source: RateSource,
+        path: Vec<u64>,
+    },
+    Error {
+        error: String,
+    },
+}
+
+#[derive(Serialize, Debug)]
+pub struct RatesResponse {
+    pub base: String,
+    pub rates: BTreeMap<String, QuoteResult>,
+}
+
+// Price base in several quote currencies at once, reporting failures per quote
+pub async fn read_rates(
+    query: web::Query<RatesQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let base: String =
+        normalize_currency(&query.base).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let quotes: Vec<&str> = query
+        .quotes
+        .split(',')
+        .map(str::trim)
+        .filter(|quote| !quote.is_empty())
+        .collect();
+    if quotes.is_empty() {
+        return Err(AppError::BadRequest(
+            "quotes must list at least one currency".to_string(),
+        ));
+    }
+
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    let rates: BTreeMap<String, QuoteResult> = quotes
+        .into_iter()
+        .map(|quote| {
+            let key: String = normalize_currency(quote).unwrap_or_else(|_| quote.to_string());
+            let result: QuoteResult = match convert_rate(&db, &base, quote) {
+                Ok(rate) => quote_result(rate),
+                Err(e) => QuoteResult::Error {
+                    error: e.to_string(),
+                },
+            };
+            (key, result)
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(RatesResponse { base, rates }))
+}
+
+fn quote_result(rate: Rate) -> QuoteResult {
+    QuoteResult::Rate {
+        rate: rate.rate,
+        derived: rate.is_derived(),
+        source: rate.source,
+        path: rate.path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::test_support::{forex_pair, init_app, test_state};
+    use actix_web::{http::StatusCode, test};
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn tests_rates_mixes_resolvable_and_unresolvable_quotes() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.25));
+        db.insert(forex_pair(2, "USD/JPY", 150.0));
+        db.insert(forex_pair(3, "EUR/GBP", 0.85));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/rates?base=eur&quotes=USD,GBP,JPY,CHF,XX")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+
+        assert_eq!(body["base"], "EUR");
+        assert_eq!(body["rates"]["USD"]["rate"], 1.25);
+        assert_eq!(body["rates"]["USD"]["derived"], false);
+        assert_eq!(body["rates"]["GBP"]["derived"], false);
+        assert_eq!(body["rates"]["JPY"]["derived"], true);
+        assert_eq!(body["rates"]["JPY"]["source"]["via"], "USD");
+        assert!(body["rates"]["CHF"]["error"].is_string());
+        assert!(body["rates"]["XX"]["error"].is_string());
+    }
+}
@@ -0,0 +1,262 @@
+use crate::auth::Admin;
+use crate::database::{Database, ForexPair, PricePoint};
+use crate::error::AppError;
+use crate::persistence::Snapshot;
+use crate::simulation::{max_synthetic_pairs, synthetic_pairs, RandomWalk};
+use crate::state::{AppState, WritePermit};
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+
+pub const MAX_RANDOM_WALK_STEPS: usize = 10_000;
+// One day between points at most, so the last timestamp stays within a few decades
+pub const MAX_RANDOM_WALK_STEP_SECS: i64 = 86_400;
+
+#[derive(Deserialize, Debug)]
+pub struct RandomWalkQuery {
+    pub id: u64,
+    pub steps: usize,
+    pub seed: u64,
+    pub drift: Option<f64>,
+    pub volatility: Option<f64>,
+    // Seconds between points
+    pub step_secs: Option<i64>,
+}
+
+// Simulated prices for a pair, starting from its current price; nothing is stored
+pub async fn random_walk(
+    query: web::Query<RandomWalkQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    if query.steps == 0 || query.steps > MAX_RANDOM_WALK_STEPS {
+        return Err(AppError::BadRequest(format!(
+            "steps must be between 1 and {}",
+            MAX_RANDOM_WALK_STEPS
+        )));
+    }
+    let step: Option<Duration> = query
+        .step_secs
+        .map(|secs| {
+            Duration::try_seconds(secs)
+                .filter(|_| (1..=MAX_RANDOM_WALK_STEP_SECS).contains(&secs))
+                .ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "step_secs must be between 1 and {}",
+                        MAX_RANDOM_WALK_STEP_SECS
+                    ))
+                })
+        })
+        .transpose()?;
+    let defaults: RandomWalk = RandomWalk::default();
+    let walk: RandomWalk = RandomWalk {
+        drift: query.drift.unwrap_or(defaults.drift),
+        volatility: query.volatility.unwrap_or(defaults.volatility),
+        step: step.unwrap_or(defaults.step),
+    };
+    if !walk.drift.is_finite() || !walk.volatility.is_finite() || walk.volatility < 0.0 {
+        return Err(AppError::BadRequest(
+            "drift must be a number and volatility a non-negative number".to_string(),
+        ));
+    }
+
+    let start_price: f64 = {
+        let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        db.get(&query.id)
+            .map(|forex_pair| forex_pair.price)
+            .ok_or_else(|| AppError::NotFound(format!("no forex pair with id {}", query.id)))?
+    };
+    let points: Vec<PricePoint> = walk.generate(start_price, Utc::now(), query.steps, query.seed);
+    Ok(HttpResponse::Ok().json(points))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeedRequest {
+    pub count: usize,
+    pub seed: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeedQuery {
+    // Replace the records the database holds instead of refusing
+    #[serde(default)]
+    pub force: bool,
+}
+
+// Fill the database with synthetic pairs for development, see simulation::synthetic_pairs.
+// 409 when it already holds records, unless ?force=true, which replaces them along with their
+// price history. Watchlists and the quarantine are kept; watched pairs the seed does not
+// create simply drop out of the watchlist until they exist again.
+pub async fn seed_database(
+    _admin: Admin,
+    app_state: web::Data<AppState>,
+    query: web::Query<SeedQuery>,
+    seed: web::Json<SeedRequest>,
+) -> Result<HttpResponse, AppError> {
+    if seed.count == 0 {
+        return Err(AppError::BadRequest("count must be at least 1".to_string()));
+    }
+    let generated: Vec<ForexPair> = synthetic_pairs(seed.count, seed.seed).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "count must be at most {}, the number of distinct pairs available",
+            max_synthetic_pairs()
+        ))
+    })?;
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (seeded, snapshot): (Vec<ForexPair>, Snapshot) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        if !db.is_empty() && !query.force {
+            return Err(AppError::Conflict(format!(
+                "database already holds {} pairs, pass ?force=true to replace them",
+                db.len()
+            )));
+        }
+        let mut fresh: Database = Database::with_path(db.database_path());
+        for forex_pair in generated {
+            forex_pair.validate(app_state.config.max_spread_pct)?;
+            fresh.insert(forex_pair);
+        }
+        let seeded: Vec<ForexPair> = fresh.iter().cloned().collect();
+        db.replace(fresh);
+        (seeded, app_state.snapshot(&db)?)
+    };
+    app_state.persist(snapshot).await?;
+    tracing::info!(count = seeded.len(), seed = seed.seed, "seeded database");
+    Ok(HttpResponse::Ok().json(seeded))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::simulation::max_synthetic_pairs;
+    use crate::test_support::{
+        admin_config, admin_header, forex_pair, init_app, temp_database, test_state,
+    };
+    use actix_web::{http::StatusCode, test};
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn tests_random_walk_same_seed_same_series() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let state = test_state(db, Config::default());
+        let app = init_app(state.clone()).await;
+
+        let prices = |body: Value| -> Vec<f64> {
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|point| point["price"].as_f64().unwrap())
+                .collect()
+        };
+        let walk = |seed: u64| {
+            test::TestRequest::get()
+                .uri(&format!(
+                    "/forex_pairs/random_walk?id=1&steps=50&seed={}",
+                    seed
+                ))
+                .to_request()
+        };
+        let first: Vec<f64> = prices(test::call_and_read_body_json(&app, walk(42)).await);
+        let second: Vec<f64> = prices(test::call_and_read_body_json(&app, walk(42)).await);
+        let other: Vec<f64> = prices(test::call_and_read_body_json(&app, walk(43)).await);
+        assert_eq!(first.len(), 50);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+
+        // Nothing was persisted
+        assert_eq!(state.db.lock().unwrap().price_history(&1).len(), 1);
+
+        for uri in [
+            "/forex_pairs/random_walk?id=1&steps=0&seed=1",
+            "/forex_pairs/random_walk?id=1&steps=5&seed=1&volatility=-1",
+            "/forex_pairs/random_walk?id=1&steps=5&seed=1&step_secs=0",
+            "/forex_pairs/random_walk?id=1&steps=5&seed=1&step_secs=-60",
+            "/forex_pairs/random_walk?id=1&steps=5&seed=1&step_secs=86401",
+            "/forex_pairs/random_walk?id=1&steps=5&seed=1&step_secs=9223372036854775807",
+            "/forex_pairs/random_walk?id=1&steps=10000&seed=1&step_secs=-9223372036854775808",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/random_walk?id=9&steps=5&seed=1")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // The widest step and the most steps still fit
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/random_walk?id=1&steps=10000&seed=1&step_secs=86400")
+            .to_request();
+        let points: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(points.as_array().unwrap().len(), 10_000);
+    }
+
+    #[actix_web::test]
+    async fn tests_seed_same_seed_same_pairs() {
+        let mut db: Database = temp_database("seed");
+        let path: std::path::PathBuf = db.database_path().to_path_buf();
+        let app = init_app(test_state(temp_database("seed_empty"), admin_config())).await;
+        let seed = |uri: &str, count: usize, seed: u64| {
+            test::TestRequest::post()
+                .uri(uri)
+                .insert_header(admin_header())
+                .set_json(json!({ "count": count, "seed": seed }))
+                .to_request()
+        };
+        // Everything but created_at, which is the time of the call
+        let without_created_at = |mut body: Value| -> Value {
+            for forex_pair in body.as_array_mut().unwrap() {
+                forex_pair.as_object_mut().unwrap().remove("created_at");
+            }
+            body
+        };
+
+        let first: Value =
+            test::call_and_read_body_json(&app, seed("/forex_pairs/seed", 20, 42)).await;
+        assert_eq!(first.as_array().unwrap().len(), 20);
+
+        // Not empty any more: refused without force
+        let resp = test::call_service(&app, seed("/forex_pairs/seed", 20, 42)).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        let second: Value =
+            test::call_and_read_body_json(&app, seed("/forex_pairs/seed?force=true", 20, 42)).await;
+        assert_eq!(
+            without_created_at(first.clone()),
+            without_created_at(second)
+        );
+        let other: Value =
+            test::call_and_read_body_json(&app, seed("/forex_pairs/seed?force=true", 20, 7)).await;
+        assert_ne!(without_created_at(first), without_created_at(other));
+
+        // More than the 30 built-in pairs, as in {"count": 100, "seed": 42}
+        let many: Value =
+            test::call_and_read_body_json(&app, seed("/forex_pairs/seed?force=true", 100, 42))
+                .await;
+        assert_eq!(many.as_array().unwrap().len(), 100);
+        let max: usize = max_synthetic_pairs();
+        let resp =
+            test::call_service(&app, seed("/forex_pairs/seed?force=true", max + 1, 42)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // Saved, and a populated database is replaced with force; watchlists survive
+        db.insert(forex_pair(999, "EUR/USD", 1.08));
+        db.add_to_watchlist("alice", "EUR/USD");
+        let app = init_app(test_state(db, admin_config())).await;
+        let resp = test::call_service(&app, seed("/forex_pairs/seed?force=true", max, 1)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let saved: Database = Database::load_from_file(&path).unwrap();
+        assert_eq!(saved.len(), max);
+        assert!(saved.get(&999).is_none());
+        assert!(saved.price_history(&999).is_empty());
+        let watched: Vec<&str> = saved
+            .watchlist("alice")
+            .iter()
+            .map(|forex_pair| forex_pair.pair.as_str())
+            .collect();
+        assert_eq!(watched, ["EUR/USD"]);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::database::Database;
+use crate::error::AppError;
+use crate::state::AppState;
+use crate::stats::{HeatmapBucket, PercentileField};
+use actix_web::{web, HttpResponse};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug)]
+pub struct PercentileQuery {
+    pub p: f64,
+    #[serde(default = "default_field")]
+    pub field: PercentileField,
+}
+
+fn default_field() -> PercentileField {
+    PercentileField::Price
+}
+
+#[derive(Serialize, Debug)]
+pub struct PercentileResponse {
+    pub field: PercentileField,
+    pub p: f64,
+    // null when no pair has a value for the field
+    pub value: Option<Decimal>,
+}
+
+// p-th percentile of a field across all pairs, p in (0, 100]
+pub async fn percentile(
+    query: web::Query<PercentileQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    if !(query.p > 0.0 && query.p <= 100.0) {
+        return Err(AppError::BadRequest(
+            "p must be in the range (0, 100]".to_string(),
+        ));
+    }
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    let value: Option<Decimal> = db.percentile(query.field, query.p);
+    Ok(HttpResponse::Ok().json(PercentileResponse {
+        field: query.field,
+        p: query.p,
+        value,
+    }))
+}
+
+// Upper bound on ?bins, so one request cannot allocate an arbitrarily large response
+pub const MAX_HEATMAP_BINS: usize = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct HeatmapQuery {
+    #[serde(default = "default_bins")]
+    pub bins: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+fn default_bins() -> usize {
+    10
+}
+
+// Price distribution for market overviews: how many pairs fall in each of `bins` equal
+// slices of [min, max]
+pub async fn heatmap(
+    query: web::Query<HeatmapQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    if !(1..=MAX_HEATMAP_BINS).contains(&query.bins) {
+        return Err(AppError::BadRequest(format!(
+            "bins must be between 1 and {}",
+            MAX_HEATMAP_BINS
+        )));
+    }
+    if !(query.min.is_finite() && query.max.is_finite() && query.min < query.max) {
+        return Err(AppError::BadRequest(
+            "min and max must be numbers with min < max".to_string(),
+        ));
+    }
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    let buckets: Vec<HeatmapBucket> = db.heatmap(query.bins, query.min, query.max);
+    Ok(HttpResponse::Ok().json(buckets))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::test_support::{forex_pair, init_app, test_state};
+    use actix_web::{http::StatusCode, test};
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn tests_percentile_endpoint() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.1));
+        db.insert(forex_pair(2, "GBP/USD", 1.3));
+        db.insert(forex_pair(3, "AUD/USD", 0.7));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/percentile?p=50&field=price")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["value"], "1.1");
+
+        for uri in [
+            "/forex_pairs/percentile?p=0",
+            "/forex_pairs/percentile?p=100.5",
+            "/forex_pairs/percentile?p=50&field=volume",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_heatmap_endpoint() {
+        let mut db: Database = Database::new();
+        let prices: [f64; 8] = [0.55, 0.66, 0.9, 1.1, 1.27, 1.6, 2.0, 151.2];
+        for (index, price) in prices.iter().enumerate() {
+            let pair: String = format!("AA{}/USD", (b'A' + index as u8) as char);
+            db.insert(forex_pair(index as u64 + 1, &pair, *price));
+        }
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pairs/heatmap?bins=10&min=0.5&max=2.0")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        let buckets: &Vec<Value> = body.as_array().unwrap();
+        assert_eq!(buckets.len(), 10);
+        assert_eq!(buckets[0]["range_start"], 0.5);
+        assert_eq!(buckets[9]["range_end"], 2.0);
+        let total: u64 = buckets
+            .iter()
+            .map(|bucket| bucket["count"].as_u64().unwrap())
+            .sum();
+        // Everything but 151.2
+        assert_eq!(total, 7);
+
+        for uri in [
+            "/forex_pairs/heatmap?bins=0&min=0.5&max=2.0",
+            "/forex_pairs/heatmap?bins=10&min=2.0&max=2.0",
+            "/forex_pairs/heatmap?bins=10&min=3&max=2.0",
+            "/forex_pairs/heatmap?bins=10&min=0.5",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+}
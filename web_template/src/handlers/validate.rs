@@ -0,0 +1,67 @@
+use crate::database::ForexPair;
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug)]
+pub struct PairInput {
+    pub pair: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PairValidation {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+// Check a pair string the way create would, without looking at or touching the database
+pub async fn validate_pair(input: web::Json<PairInput>) -> impl Responder {
+    let result: PairValidation = match ForexPair::normalize_pair(&input.pair) {
+        Ok(normalized) => PairValidation {
+            valid: true,
+            normalized: Some(normalized),
+            reason: None,
+        },
+        Err(reason) => PairValidation {
+            valid: false,
+            normalized: None,
+            reason: Some(reason),
+        },
+    };
+    HttpResponse::Ok().json(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::test_support::{init_app, test_state};
+    use actix_web::test;
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn tests_validate_pair() {
+        let state = test_state(Database::new(), Config::default());
+        let app = init_app(state.clone()).await;
+        let validate = |pair: &str| {
+            test::TestRequest::post()
+                .uri("/validate/pair")
+                .set_json(json!({ "pair": pair }))
+                .to_request()
+        };
+
+        let body: Value = test::call_and_read_body_json(&app, validate("EUR/USD")).await;
+        assert_eq!(body, json!({ "valid": true, "normalized": "EUR/USD" }));
+
+        let body: Value = test::call_and_read_body_json(&app, validate("eur-usd")).await;
+        assert_eq!(body, json!({ "valid": true, "normalized": "EUR/USD" }));
+
+        let body: Value = test::call_and_read_body_json(&app, validate("EUR/EUR")).await;
+        assert_eq!(body["valid"], false);
+        assert!(body["reason"].as_str().unwrap().contains("same currency"));
+
+        assert!(state.db.lock().unwrap().is_empty());
+    }
+}
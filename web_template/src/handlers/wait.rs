@@ -0,0 +1,183 @@
+use crate::database::{Database, ForexPair};
+use crate::error::AppError;
+use crate::events::{ForexPairEvent, ForexPairEventData};
+use crate::state::AppState;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+pub const DEFAULT_WAIT_TIMEOUT_SECS: f64 = 30.0;
+pub const MAX_WAIT_TIMEOUT_SECS: f64 = 120.0;
+
+// Actor attached to alerts generated by the server rather than a client
+const ALERT_ACTOR: &str = "system";
+
+#[derive(Deserialize, Debug)]
+pub struct WaitQuery {
+    pub above: Option<f64>,
+    pub below: Option<f64>,
+    // Seconds, capped at MAX_WAIT_TIMEOUT_SECS
+    pub timeout: Option<f64>,
+}
+
+impl WaitQuery {
+    // The threshold the price has reached, if any
+    fn crossed(&self, price: f64) -> Option<f64> {
+        match (self.above, self.below) {
+            (Some(above), _) if price >= above => Some(above),
+            (_, Some(below)) if price <= below => Some(below),
+            _ => None,
+        }
+    }
+}
+
+// Long-poll until the price of {id} reaches ?above= or ?below=, answering with a price_alert
+// event, or 204 once ?timeout= seconds pass. A price already past the threshold answers at
+// once. Nothing is spawned: a client disconnect drops this future and its receiver with it.
+pub async fn wait_for_price(
+    app_state: web::Data<AppState>,
+    id: web::Path<u64>,
+    query: web::Query<WaitQuery>,
+) -> Result<HttpResponse, AppError> {
+    let id: u64 = id.into_inner();
+    if query.above.is_none() && query.below.is_none() {
+        return Err(AppError::BadRequest(
+            "set at least one of above or below".to_string(),
+        ));
+    }
+    let timeout: f64 = query.timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT_SECS);
+    if !timeout.is_finite() || timeout < 0.0 {
+        return Err(AppError::BadRequest(
+            "timeout must be a non-negative number of seconds".to_string(),
+        ));
+    }
+    let timeout: Duration = Duration::from_secs_f64(timeout.min(MAX_WAIT_TIMEOUT_SECS));
+
+    // Subscribe before reading the current price so an update in between is not missed
+    let mut events: Receiver<ForexPairEvent> = app_state.events.subscribe();
+    let current: ForexPair = current_pair(&app_state, id)?;
+    if let Some(threshold) = query.crossed(current.price) {
+        return Ok(alert(&app_state, &current, threshold));
+    }
+
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            _ = &mut deadline => return Ok(HttpResponse::NoContent().finish()),
+            received = events.recv() => match received {
+                Ok(event) if event.pair_id == id => match event.data {
+                    ForexPairEventData::Created { forex_pair }
+                    | ForexPairEventData::Updated { after: forex_pair, .. } => {
+                        if let Some(threshold) = query.crossed(forex_pair.price) {
+                            return Ok(alert(&app_state, &forex_pair, threshold));
+                        }
+                    }
+                    // A patch only carries the changed fields, read the record back for the rest
+                    ForexPairEventData::Patched { changes }
+                        if changes.iter().any(|diff| diff.field == "price") =>
+                    {
+                        let current: ForexPair = current_pair(&app_state, id)?;
+                        if let Some(threshold) = query.crossed(current.price) {
+                            return Ok(alert(&app_state, &current, threshold));
+                        }
+                    }
+                    ForexPairEventData::Deleted { .. } => {
+                        return Err(AppError::NotFound(format!("forex pair {} was deleted", id)));
+                    }
+                    _ => {}
+                },
+                Ok(_) => {}
+                // Missed some events, fall back to the stored price
+                Err(RecvError::Lagged(_)) => {
+                    let current: ForexPair = current_pair(&app_state, id)?;
+                    if let Some(threshold) = query.crossed(current.price) {
+                        return Ok(alert(&app_state, &current, threshold));
+                    }
+                }
+                Err(RecvError::Closed) => return Ok(HttpResponse::NoContent().finish()),
+            },
+        }
+    }
+}
+
+fn current_pair(app_state: &AppState, id: u64) -> Result<ForexPair, AppError> {
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    db.get(&id)
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("no forex pair with id {}", id)))
+}
+
+fn alert(app_state: &AppState, forex_pair: &ForexPair, threshold: f64) -> HttpResponse {
+    app_state.metrics.record_price_alert(forex_pair.into());
+    HttpResponse::Ok().json(ForexPairEvent::price_alert(
+        ALERT_ACTOR,
+        forex_pair,
+        threshold,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::test_support::{forex_pair, init_app, temp_database, test_state};
+    use actix_web::{http::StatusCode, test};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn tests_price_update_releases_waiter() {
+        let mut db: Database = temp_database("wait");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let wait = test::TestRequest::get()
+            .uri("/forex_pair/1/wait?above=1.10&timeout=5")
+            .to_request();
+        let update = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            // Below the threshold: the waiter keeps waiting
+            for price in [1.09, 1.11] {
+                let req = test::TestRequest::put()
+                    .uri("/forex_pair")
+                    .set_json(json!({ "id": 1, "pair": "EUR/USD", "price": price }))
+                    .to_request();
+                assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+            }
+        };
+        let (resp, ()) = tokio::join!(test::call_service(&app, wait), update);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let event: Value = test::read_body_json(resp).await;
+        assert_eq!(event["event_type"], "price_alert");
+        assert_eq!(event["pair_id"], 1);
+        assert_eq!(event["data"]["price_alert"]["price"], 1.11);
+        assert_eq!(event["data"]["price_alert"]["threshold"], 1.10);
+    }
+
+    #[actix_web::test]
+    async fn tests_wait_times_out_with_204() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/1/wait?below=1.0&timeout=0.05")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/1/wait?above=1.0")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/9/wait?above=1.0")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
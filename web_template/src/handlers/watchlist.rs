@@ -0,0 +1,138 @@
+use crate::database::{Database, ForexPair};
+use crate::error::AppError;
+use crate::persistence::Snapshot;
+use crate::state::{AppState, WritePermit};
+use actix_web::{web, HttpResponse};
+
+// Add a pair (any accepted spelling, e.g. EUR/USD or eurusd) to a user's favourites
+pub async fn add_to_watchlist(
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, AppError> {
+    let (user_id, pair): (String, String) = path.into_inner();
+    let pair: String = ForexPair::normalize_pair(&pair).map_err(AppError::BadRequest)?;
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (watchlist, snapshot): (Vec<ForexPair>, Option<Snapshot>) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        if db.find_by_pair(&pair).is_none() {
+            return Err(AppError::NotFound(format!("no forex pair {}", pair)));
+        }
+        let snapshot: Option<Snapshot> = match db.add_to_watchlist(&user_id, &pair) {
+            true => Some(app_state.snapshot(&db)?),
+            false => None,
+        };
+        (owned(db.watchlist(&user_id)), snapshot)
+    };
+    if let Some(snapshot) = snapshot {
+        app_state.persist(snapshot).await?;
+    }
+    Ok(HttpResponse::Ok().json(watchlist))
+}
+
+pub async fn remove_from_watchlist(
+    app_state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, AppError> {
+    let (user_id, pair): (String, String) = path.into_inner();
+    let pair: String = ForexPair::normalize_pair(&pair).map_err(AppError::BadRequest)?;
+    let _permit: WritePermit = app_state.try_acquire_write()?;
+    let (watchlist, snapshot): (Vec<ForexPair>, Option<Snapshot>) = {
+        let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+        let snapshot: Option<Snapshot> = match db.remove_from_watchlist(&user_id, &pair) {
+            true => Some(app_state.snapshot(&db)?),
+            false => None,
+        };
+        (owned(db.watchlist(&user_id)), snapshot)
+    };
+    if let Some(snapshot) = snapshot {
+        app_state.persist(snapshot).await?;
+    }
+    Ok(HttpResponse::Ok().json(watchlist))
+}
+
+pub async fn read_watchlist(
+    app_state: web::Data<AppState>,
+    user_id: web::Path<String>,
+) -> HttpResponse {
+    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
+    HttpResponse::Ok().json(db.watchlist(&user_id))
+}
+
+fn owned(forex_pairs: Vec<&ForexPair>) -> Vec<ForexPair> {
+    forex_pairs.into_iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::test_support::{forex_pair, init_app, temp_database, test_state};
+    use actix_web::{http::StatusCode, test};
+    use serde_json::Value;
+
+    fn pairs(body: &Value) -> Vec<&str> {
+        body.as_array()
+            .unwrap()
+            .iter()
+            .map(|forex_pair| forex_pair["pair"].as_str().unwrap())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn tests_watchlist_add_remove_and_list() {
+        let mut db: Database = temp_database("watchlist");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        db.insert(forex_pair(3, "USD/JPY", 151.2));
+        let path = db.database_path().to_path_buf();
+        let app = init_app(test_state(db, Config::default())).await;
+
+        for uri in [
+            "/users/alice/watchlist/USD/JPY",
+            "/users/alice/watchlist/eurusd",
+            "/users/alice/watchlist/EUR-USD",
+            "/users/bob/watchlist/GBP/USD",
+        ] {
+            let req = test::TestRequest::post().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+        }
+        let req = test::TestRequest::post()
+            .uri("/users/alice/watchlist/AUD/USD")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get()
+            .uri("/users/alice/watchlist")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pairs(&body), ["EUR/USD", "USD/JPY"]);
+        assert_eq!(body[0]["price"], 1.08);
+
+        let req = test::TestRequest::delete()
+            .uri("/users/alice/watchlist/EUR/USD")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(pairs(&body), ["USD/JPY"]);
+
+        let saved: Database = Database::load_from_file(&path).unwrap();
+        assert_eq!(
+            saved
+                .watchlist("alice")
+                .iter()
+                .map(|p| p.id)
+                .collect::<Vec<u64>>(),
+            [3]
+        );
+        assert_eq!(
+            saved
+                .watchlist("bob")
+                .iter()
+                .map(|p| p.id)
+                .collect::<Vec<u64>>(),
+            [2]
+        );
+        assert!(saved.watchlist("carol").is_empty());
+    }
+}
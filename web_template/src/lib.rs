@@ -0,0 +1,29 @@
+pub mod audit;
+pub mod auth;
+pub mod bootstrap;
+pub mod breaker;
+pub mod config;
+pub mod conversions;
+pub mod converter;
+pub mod database;
+pub mod error;
+pub mod events;
+pub mod handlers;
+pub mod locale;
+pub mod logging;
+pub mod maintenance;
+pub mod metrics;
+pub mod middleware;
+pub mod paths;
+pub mod persistence;
+pub mod provider;
+pub mod routes;
+pub mod signing;
+pub mod simulation;
+pub mod state;
+pub mod stats;
+pub mod validation;
+pub mod webhook;
+
+#[cfg(test)]
+mod test_support;
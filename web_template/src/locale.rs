@@ -0,0 +1,103 @@
+use crate::database::ForexPair;
+use serde::Serialize;
+
+// Grouping and decimal separators for a locale, keyed by its BCP 47 tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub grouping: &'static str,
+    pub decimal: &'static str,
+}
+
+const LOCALES: &[(&str, NumberFormat)] = &[
+    ("en-US", separators(",", ".")),
+    ("en-GB", separators(",", ".")),
+    ("ja-JP", separators(",", ".")),
+    ("de-DE", separators(".", ",")),
+    ("es-ES", separators(".", ",")),
+    ("it-IT", separators(".", ",")),
+    ("nl-NL", separators(".", ",")),
+    // Narrow no-break space, as CLDR has it
+    ("fr-FR", separators("\u{202f}", ",")),
+    ("de-CH", separators("\u{2019}", ".")),
+];
+
+const fn separators(grouping: &'static str, decimal: &'static str) -> NumberFormat {
+    NumberFormat { grouping, decimal }
+}
+
+// Look a locale tag up case-insensitively, accepting `de_DE` as well as `de-DE`
+pub fn number_format(locale: &str) -> Option<NumberFormat> {
+    let locale: String = locale.trim().replace('_', "-");
+    LOCALES
+        .iter()
+        .find(|(tag, _)| tag.eq_ignore_ascii_case(&locale))
+        .map(|(_, format)| *format)
+}
+
+// Display string for a price: all significant digits are kept, only the separators change.
+// Unknown locales get the plain `{}` formatting.
+pub fn format_price(price: f64, locale: &str) -> String {
+    let plain: String = price.to_string();
+    let Some(format) = number_format(locale) else {
+        return plain;
+    };
+    if !price.is_finite() {
+        return plain;
+    }
+
+    let (sign, digits): (&str, &str) = match plain.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", plain.as_str()),
+    };
+    let (integer, fraction): (&str, Option<&str>) = match digits.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (digits, None),
+    };
+
+    let mut out: String = sign.to_string();
+    for (index, digit) in integer.chars().enumerate() {
+        if index > 0 && (integer.len() - index) % 3 == 0 {
+            out.push_str(format.grouping);
+        }
+        out.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        out.push_str(format.decimal);
+        out.push_str(fraction);
+    }
+    out
+}
+
+// A pair as returned by the GET routes when a locale applies; `price` stays numeric
+#[derive(Serialize, Debug)]
+pub struct LocalizedForexPair<'a> {
+    #[serde(flatten)]
+    pub forex_pair: &'a ForexPair,
+    pub formatted_price: String,
+}
+
+impl<'a> LocalizedForexPair<'a> {
+    pub fn new(forex_pair: &'a ForexPair, locale: &str) -> Self {
+        Self {
+            forex_pair,
+            formatted_price: format_price(forex_pair.price, locale),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_format_price_en_us_and_de_de() {
+        assert_eq!(format_price(1234567.891, "en-US"), "1,234,567.891");
+        assert_eq!(format_price(1234567.891, "de-DE"), "1.234.567,891");
+        assert_eq!(format_price(1.0842, "de_de"), "1,0842");
+        assert_eq!(format_price(-151.2, "en-US"), "-151.2");
+        assert_eq!(format_price(1000.0, "de-DE"), "1.000");
+
+        // Unknown locales fall back to the plain format
+        assert_eq!(format_price(1234.5, "xx-XX"), "1234.5");
+    }
+}
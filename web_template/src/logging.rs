@@ -0,0 +1,66 @@
+use std::path::Path;
+use tracing_subscriber::EnvFilter;
+
+// Used when RUST_LOG is unset or does not parse
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+// Install the global subscriber; call once, first thing in main
+pub fn init() {
+    let filter: EnvFilter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+}
+
+// The banner logged once the server is bound
+pub fn log_startup(bind_address: &str, database_path: &Path) {
+    tracing::info!(
+        bind_address,
+        database_path = %database_path.display(),
+        version = env!("CARGO_PKG_VERSION"),
+        "web_template {} listening on {}",
+        env!("CARGO_PKG_VERSION"),
+        bind_address
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    // Collects formatted log output so the test can read it back
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tests_startup_banner_names_the_bind_address() {
+        let captured: Captured = Captured::default();
+        let writer: Captured = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new(DEFAULT_LOG_FILTER))
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            log_startup("127.0.0.1:8080", Path::new("data/database.json"));
+        });
+
+        let output: String = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("INFO"));
+        assert!(output.contains("listening on 127.0.0.1:8080"));
+        assert!(output.contains("data/database.json"));
+        assert!(output.contains(env!("CARGO_PKG_VERSION")));
+    }
+}
@@ -1,138 +1,61 @@
 use actix_cors::Cors;
-use actix_web::{web, App, HttpServer, http::header, Responder, HttpResponse};
-use serde::{Deserialize, Serialize};
-use reqwest::Client as HttpClient;
-use async_trait::async_trait;
-use std::hash::Hash;
-use std::sync::Mutex;
-use std::collections::HashMap;
-use std::fs;
-use std::io::Write;
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct ForexPair {
-    id: u64,
-    pair: String,
-    price: f64
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Database {
-    forex_pairs: HashMap<u64, ForexPair>,
-}
-impl Database {
-    fn new() -> Self {
-        Self {
-            forex_pairs: HashMap::new(),
-        }
-    }
-
-    fn insert(&mut self, forex_pair: ForexPair) -> Option<ForexPair> {
-        self.forex_pairs.insert(forex_pair.id, forex_pair)
-    }
-
-    fn get(&self, id: &u64) -> Option<&ForexPair> {
-        self.forex_pairs.get(id)
-    }
-
-    fn get_all(&self) -> Vec<&ForexPair> {
-        self.forex_pairs.values().collect()
-    }
-
-    fn delete(&mut self, id: &u64) {
-        self.forex_pairs.remove(id);
-    }
-
-    fn update(&mut self, forex_pair: ForexPair) {
-        self.forex_pairs.insert(forex_pair.id, forex_pair);
-    }
-
-    // DATABASE SAVING
-    fn save_to_file(&self) -> std::io::Result<()> {
-        let data: String = serde_json::to_string(&self)?;
-        let mut file: fs::File = fs::File::create("database.json")?;
-        file.write_all(data.as_bytes())?;
-        Ok(())
-    }
-
-    fn load_from_file() -> std::io::Result<Self> {
-        let file_contents: String = fs::read_to_string("database.json")?;
-        let db: Database = serde_json::from_str(&file_contents)?;
-        Ok(db)
-    }
-}
-
-struct AppState {
-    db: Mutex<Database>
-}
-
-async fn create_forex_pair(app_state: web::Data<AppState>, forex_pair: web::Json<ForexPair>) -> impl Responder {
-    let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
-    db.insert(forex_pair.into_inner());
-    let _ = db.save_to_file().unwrap();
-    HttpResponse::Ok().finish()
-}
-
-async fn read_forex_pair(app_state: web::Data<AppState>, id: web::Path<u64>) -> impl Responder {
-    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
-    match db.get(&id.into_inner()) {
-        Some(forex_pair) => HttpResponse::Ok().json(forex_pair),
-        None => HttpResponse::NotFound().finish()
-    }
-}
-
-async fn read_all_forex_pairs(app_state: web::Data<AppState>) -> impl Responder {
-    let db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
-    let forex_pairs = db.get_all();
-    HttpResponse::Ok().json(forex_pairs)
-}
-
-async fn update_forex_pair(app_state: web::Data<AppState>, forex_pair: web::Json<ForexPair>) -> impl Responder {
-    let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
-    db.update(forex_pair.into_inner());
-    let _ = db.save_to_file().unwrap();
-    HttpResponse::Ok().finish()
-}
-
-async fn delete_forex_pair(app_state: web::Data<AppState>, id: web::Path<u64>) -> impl Responder {
-    let mut db: std::sync::MutexGuard<Database> = app_state.db.lock().unwrap();
-    db.delete(&id.into_inner());
-    let _ = db.save_to_file().unwrap();
-    HttpResponse::Ok().finish()
-}
+use actix_web::dev::Server;
+use actix_web::{http::header, web, HttpServer};
+use dotenv::dotenv;
+use web_template::bootstrap::bootstrap;
+use web_template::config::Config;
+use web_template::database::Database;
+use web_template::events::ForexPairEvent;
+use web_template::logging;
+use web_template::middleware::new_app;
+use web_template::routes;
+use web_template::state::AppState;
+
+const BIND_ADDRESS: &str = "127.0.0.1:8080";
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let db: Database = match Database::load_from_file() {
-        Ok(db) => db,
-        Err(_) => Database::new()
-    };
+    dotenv().ok();
+    logging::init();
+    let config: Config = Config::from_env();
 
-    let data: web::Data<AppState> = web::Data::new(AppState {
-        db: Mutex::new(db)
-    });
+    let (db, _created): (Database, bool) = Database::load_or_create(&config.database_path);
 
-    HttpServer::new(move || {
-        App::new()
+    let data: web::Data<AppState> = web::Data::new(AppState::new(db, config));
+    bootstrap(&data).await;
+    let app_data: web::Data<AppState> = data.clone();
+
+    let server: Server = HttpServer::new(move || {
+        new_app()
             .wrap(
                 Cors::permissive()
-                .allowed_origin_fn(|origin, _req_head| {
-                    origin.as_bytes().starts_with(b"http://localhost") || origin == "null"
-                })
-                .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
-                .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
-                .allowed_header(header::CONTENT_TYPE)
-                .supports_credentials()
-                .max_age(3600)
+                    .allowed_origin_fn(|origin, _req_head| {
+                        origin.as_bytes().starts_with(b"http://localhost") || origin == "null"
+                    })
+                    .allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE"])
+                    .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
+                    .allowed_header(header::CONTENT_TYPE)
+                    .supports_credentials()
+                    .max_age(3600),
             )
-            .app_data(data.clone())
-            .route("/forex_pair", web::post().to(create_forex_pair))
-            .route("/forex_pairs", web::get().to(read_all_forex_pairs))
-            .route("/forex_pair", web::put().to(update_forex_pair))
-            .route("/forex_pair/{id}", web::get().to(read_forex_pair))
-            .route("/forex_pair/{id}", web::delete().to(delete_forex_pair))
+            .app_data(app_data.clone())
+            .configure(|cfg| routes::configure(cfg, &app_data.config))
     })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
-}
\ No newline at end of file
+    .bind(BIND_ADDRESS)?
+    .run();
+    logging::log_startup(BIND_ADDRESS, &data.config.database_path);
+
+    // Sent alongside the running server so a slow receiver never delays serving
+    let startup: ForexPairEvent =
+        ForexPairEvent::startup(data.db.lock().unwrap().len(), BIND_ADDRESS);
+    let startup_state: web::Data<AppState> = data.clone();
+    actix_web::rt::spawn(async move { startup_state.notify_lifecycle(startup).await });
+
+    let result: std::io::Result<()> = server.await;
+
+    // Bounded by WEBHOOK_TIMEOUT_MS, so this cannot hold up the exit for long
+    let shutdown: ForexPairEvent =
+        ForexPairEvent::shutdown(data.uptime().as_secs(), data.db.lock().unwrap().len());
+    data.notify_lifecycle(shutdown).await;
+    result
+}
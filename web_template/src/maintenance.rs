@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// One scheduled read-only window, [start, end)
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl MaintenanceWindow {
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        self.start <= now && now < self.end
+    }
+}
+
+// Parse MAINTENANCE_WINDOWS: comma separated `start/end` RFC 3339 intervals, e.g.
+// `2026-01-01T02:00:00Z/2026-01-01T03:00:00Z`
+pub fn parse_windows(raw: &str) -> Result<Vec<MaintenanceWindow>, String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (start, end) = entry
+                .split_once('/')
+                .ok_or_else(|| format!("window {:?} must be written as start/end", entry))?;
+            let parse = |value: &str| {
+                DateTime::parse_from_rfc3339(value.trim())
+                    .map(|time| time.with_timezone(&Utc))
+                    .map_err(|err| format!("invalid time {:?}: {}", value, err))
+            };
+            let window: MaintenanceWindow = MaintenanceWindow {
+                start: parse(start)?,
+                end: parse(end)?,
+            };
+            if window.end <= window.start {
+                return Err(format!("window {:?} ends before it starts", entry));
+            }
+            Ok(window)
+        })
+        .collect()
+}
+
+#[derive(Serialize, Debug)]
+pub struct MaintenanceStatus {
+    pub read_only: bool,
+    // Set through POST /admin/maintenance, independent of the schedule
+    pub manual: bool,
+    pub active_window: Option<MaintenanceWindow>,
+    pub next_window: Option<MaintenanceWindow>,
+    // Seconds until the active window closes; absent for manual read-only mode
+    pub retry_after: Option<u64>,
+}
+
+// Runtime read-only switch plus the configured schedule
+#[derive(Debug, Default)]
+pub struct Maintenance {
+    windows: Vec<MaintenanceWindow>,
+    manual: AtomicBool,
+}
+
+impl Maintenance {
+    pub fn new(windows: Vec<MaintenanceWindow>) -> Self {
+        Self {
+            windows,
+            manual: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.manual.store(read_only, Ordering::SeqCst);
+    }
+
+    // Windows are checked on every call, so entering and leaving them needs no timer
+    pub fn status_at(&self, now: DateTime<Utc>) -> MaintenanceStatus {
+        let manual: bool = self.manual.load(Ordering::SeqCst);
+        let active_window: Option<MaintenanceWindow> = self
+            .windows
+            .iter()
+            .filter(|window| window.contains(now))
+            .max_by_key(|window| window.end)
+            .copied();
+        let next_window: Option<MaintenanceWindow> = self
+            .windows
+            .iter()
+            .filter(|window| window.start > now)
+            .min_by_key(|window| window.start)
+            .copied();
+        let retry_after: Option<u64> = active_window.map(|window| {
+            let remaining_ms: i64 = (window.end - now).num_milliseconds();
+            // Round up so clients never retry before the window has closed
+            ((remaining_ms + 999) / 1000).max(1) as u64
+        });
+
+        MaintenanceStatus {
+            read_only: manual || active_window.is_some(),
+            manual,
+            active_window,
+            next_window,
+            retry_after,
+        }
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        self.status_at(Utc::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn tests_parse_windows() {
+        let windows: Vec<MaintenanceWindow> = parse_windows(
+            "2026-01-01T02:00:00Z/2026-01-01T03:00:00Z, 2026-01-02T02:00:00+01:00/2026-01-02T02:30:00+01:00",
+        )
+        .unwrap();
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[1].start.to_rfc3339(), "2026-01-02T01:00:00+00:00");
+
+        assert!(parse_windows("").unwrap().is_empty());
+        assert!(parse_windows("2026-01-01T02:00:00Z").is_err());
+        assert!(parse_windows("2026-01-01T03:00:00Z/2026-01-01T02:00:00Z").is_err());
+    }
+
+    #[test]
+    fn tests_status_follows_schedule() {
+        let start: DateTime<Utc> = Utc::now();
+        let window: MaintenanceWindow = MaintenanceWindow {
+            start,
+            end: start + Duration::seconds(90),
+        };
+        let maintenance: Maintenance = Maintenance::new(vec![window]);
+
+        let before: MaintenanceStatus = maintenance.status_at(start - Duration::seconds(1));
+        assert!(!before.read_only);
+        assert_eq!(before.next_window, Some(window));
+
+        let during: MaintenanceStatus = maintenance.status_at(start + Duration::seconds(30));
+        assert!(during.read_only);
+        assert_eq!(during.retry_after, Some(60));
+
+        let after: MaintenanceStatus = maintenance.status_at(window.end);
+        assert!(!after.read_only);
+        assert_eq!(after.next_window, None);
+
+        maintenance.set_read_only(true);
+        let manual: MaintenanceStatus = maintenance.status_at(window.end);
+        assert!(manual.read_only && manual.manual);
+        assert_eq!(manual.retry_after, None);
+    }
+}
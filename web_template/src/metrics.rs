@@ -0,0 +1,414 @@
+use crate::database::ForexPair;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+// Upper bounds of the latency buckets in milliseconds; one more bucket catches the rest
+pub const LATENCY_BUCKETS_MS: [f64; 12] = [
+    1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+];
+
+// Process wide counters and gauges, rendered at GET /metrics in the Prometheus text format
+#[derive(Debug, Default)]
+pub struct Metrics {
+    // Writes currently holding a permit from AppState::write_semaphore
+    write_queue_depth: AtomicUsize,
+    request_count: AtomicU64,
+    // Responses with a 4xx or 5xx status
+    error_count: AtomicU64,
+    total_latency_us: AtomicU64,
+    // Generation of the last snapshot on disk, compared with the latest one taken for `dirty`
+    saved_generation: AtomicU64,
+    last_save: Mutex<Option<DateTime<Utc>>>,
+    // (method, route pattern) -> latencies. The read lock covers every request after a
+    // route's first one, which only needs the write lock to add its histogram.
+    route_latency: RwLock<HashMap<(String, String), Arc<LatencyHistogram>>>,
+    // Per pair: stored price changes, and price alerts answered by the wait route
+    price_changes: Mutex<BTreeMap<PrometheusLabels, u64>>,
+    price_alerts: Mutex<BTreeMap<PrometheusLabels, u64>>,
+}
+
+// The labels every per-pair series carries
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PrometheusLabels {
+    pub pair: String,
+    pub id_str: String,
+}
+
+impl From<&ForexPair> for PrometheusLabels {
+    fn from(forex_pair: &ForexPair) -> Self {
+        Self {
+            pair: forex_pair.pair.clone(),
+            id_str: forex_pair.id.to_string(),
+        }
+    }
+}
+
+impl PrometheusLabels {
+    // `pair="EUR/USD",id="1"`, for inside the braces
+    pub fn render(&self) -> String {
+        format!(
+            "pair=\"{}\",id=\"{}\"",
+            escape_label(&self.pair),
+            escape_label(&self.id_str)
+        )
+    }
+}
+
+// Fixed buckets allocated up front, so recording is a few atomic adds
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl LatencyHistogram {
+    pub fn record(&self, latency: Duration) {
+        let ms: f64 = latency.as_secs_f64() * 1000.0;
+        let bucket: usize = LATENCY_BUCKETS_MS.partition_point(|bound| *bound < ms);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    // Estimated like Prometheus' histogram_quantile: linear within the bucket holding the
+    // rank. Anything in the overflow bucket reports the largest bound.
+    pub fn percentile_ms(&self, p: f64) -> f64 {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+        let rank: f64 = p / 100.0 * total as f64;
+        let mut below: u64 = 0;
+        for (index, count) in counts.iter().enumerate() {
+            if *count > 0 && (below + count) as f64 >= rank {
+                let Some(upper) = LATENCY_BUCKETS_MS.get(index) else {
+                    break;
+                };
+                let lower: f64 = index
+                    .checked_sub(1)
+                    .map_or(0.0, |previous| LATENCY_BUCKETS_MS[previous]);
+                return lower + (upper - lower) * ((rank - below as f64) / *count as f64);
+            }
+            below += count;
+        }
+        LATENCY_BUCKETS_MS[LATENCY_BUCKETS_MS.len() - 1]
+    }
+}
+
+// Latency percentiles of one route, as listed in MetricsSnapshot
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RouteLatency {
+    pub method: String,
+    pub route: String,
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+}
+
+// Point-in-time copy of every metric, see AppState::metrics
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MetricsSnapshot {
+    pub request_count: u64,
+    pub error_count: u64,
+    pub avg_latency_ms: f64,
+    pub pair_count: usize,
+    // Changes made in memory that are not on disk yet
+    pub dirty: bool,
+    pub last_save: Option<DateTime<Utc>>,
+    // Sorted by route, then method
+    pub routes: Vec<RouteLatency>,
+}
+
+impl Metrics {
+    pub fn write_started(&self) {
+        self.write_queue_depth.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn write_finished(&self) {
+        self.write_queue_depth.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn write_queue_depth(&self) -> usize {
+        self.write_queue_depth.load(Ordering::SeqCst)
+    }
+
+    pub fn request_finished(&self, latency: Duration, is_error: bool) {
+        self.request_count.fetch_add(1, Ordering::SeqCst);
+        if is_error {
+            self.error_count.fetch_add(1, Ordering::SeqCst);
+        }
+        self.total_latency_us
+            .fetch_add(latency.as_micros() as u64, Ordering::SeqCst);
+    }
+
+    // route is the matched pattern (e.g. /forex_pair/{id}) so ids don't make every path new
+    pub fn record_route(&self, method: &str, route: &str, latency: Duration) {
+        let key: (String, String) = (method.to_string(), route.to_string());
+        let existing: Option<Arc<LatencyHistogram>> =
+            self.route_latency.read().unwrap().get(&key).cloned();
+        let histogram: Arc<LatencyHistogram> = match existing {
+            Some(histogram) => histogram,
+            None => self
+                .route_latency
+                .write()
+                .unwrap()
+                .entry(key)
+                .or_default()
+                .clone(),
+        };
+        histogram.record(latency);
+    }
+
+    pub fn record_price_change(&self, labels: PrometheusLabels) {
+        *self
+            .price_changes
+            .lock()
+            .unwrap()
+            .entry(labels)
+            .or_default() += 1;
+    }
+
+    pub fn record_price_alert(&self, labels: PrometheusLabels) {
+        *self.price_alerts.lock().unwrap().entry(labels).or_default() += 1;
+    }
+
+    fn route_histograms(&self) -> Vec<((String, String), Arc<LatencyHistogram>)> {
+        let mut routes: Vec<((String, String), Arc<LatencyHistogram>)> = self
+            .route_latency
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, histogram)| (key.clone(), histogram.clone()))
+            .collect();
+        routes.sort_by(|((a_method, a_route), _), ((b_method, b_route), _)| {
+            a_route.cmp(b_route).then(a_method.cmp(b_method))
+        });
+        routes
+    }
+
+    pub fn route_latencies(&self) -> Vec<RouteLatency> {
+        self.route_histograms()
+            .into_iter()
+            .map(|((method, route), histogram)| RouteLatency {
+                method,
+                route,
+                count: histogram.count(),
+                p50_ms: histogram.percentile_ms(50.0),
+                p95_ms: histogram.percentile_ms(95.0),
+                p99_ms: histogram.percentile_ms(99.0),
+            })
+            .collect()
+    }
+
+    pub fn request_count(&self) -> u64 {
+        self.request_count.load(Ordering::SeqCst)
+    }
+
+    pub fn error_count(&self) -> u64 {
+        self.error_count.load(Ordering::SeqCst)
+    }
+
+    pub fn avg_latency_ms(&self) -> f64 {
+        match self.request_count() {
+            0 => 0.0,
+            count => self.total_latency_us.load(Ordering::SeqCst) as f64 / count as f64 / 1000.0,
+        }
+    }
+
+    pub fn record_save(&self, generation: u64) {
+        self.set_saved_generation(generation);
+        *self.last_save.lock().unwrap() = Some(Utc::now());
+    }
+
+    // The file matches this generation without a save, e.g. right after a reload
+    pub fn set_saved_generation(&self, generation: u64) {
+        self.saved_generation
+            .fetch_max(generation, Ordering::SeqCst);
+    }
+
+    pub fn saved_generation(&self) -> u64 {
+        self.saved_generation.load(Ordering::SeqCst)
+    }
+
+    pub fn last_save(&self) -> Option<DateTime<Utc>> {
+        *self.last_save.lock().unwrap()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out: String = String::new();
+        gauge(
+            &mut out,
+            "write_queue_depth",
+            "Database writes currently in flight",
+            self.write_queue_depth(),
+        );
+        counter(
+            &mut out,
+            "http_requests_total",
+            "Requests answered",
+            self.request_count(),
+        );
+        counter(
+            &mut out,
+            "http_errors_total",
+            "Requests answered with a 4xx or 5xx status",
+            self.error_count(),
+        );
+        gauge(
+            &mut out,
+            "http_request_latency_avg_ms",
+            "Mean request latency in milliseconds",
+            self.avg_latency_ms(),
+        );
+        self.render_route_latency(&mut out);
+        labelled_counter(
+            &mut out,
+            "forex_price_changes_total",
+            "Stored price changes per pair",
+            &self.price_changes.lock().unwrap(),
+        );
+        labelled_counter(
+            &mut out,
+            "forex_price_alerts_total",
+            "Price alerts answered per pair",
+            &self.price_alerts.lock().unwrap(),
+        );
+        out
+    }
+
+    fn render_route_latency(&self, out: &mut String) {
+        let routes: Vec<((String, String), Arc<LatencyHistogram>)> = self.route_histograms();
+        if routes.is_empty() {
+            return;
+        }
+        let name: &str = "http_route_latency_ms";
+        let _ = writeln!(
+            out,
+            "# HELP {} Request latency per route in milliseconds",
+            name
+        );
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for ((method, route), histogram) in &routes {
+            let labels: String = format!(
+                "method=\"{}\",route=\"{}\"",
+                escape_label(method),
+                escape_label(route)
+            );
+            let mut cumulative: u64 = 0;
+            for (index, bucket) in histogram.buckets.iter().enumerate() {
+                cumulative += bucket.load(Ordering::Relaxed);
+                let le: String = LATENCY_BUCKETS_MS
+                    .get(index)
+                    .map_or("+Inf".to_string(), |bound| bound.to_string());
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{{},le=\"{}\"}} {}",
+                    name, labels, le, cumulative
+                );
+            }
+            let sum_ms: f64 = histogram.sum_us.load(Ordering::Relaxed) as f64 / 1000.0;
+            let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, sum_ms);
+            let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count());
+        }
+
+        let name: &str = "http_route_latency_quantile_ms";
+        let _ = writeln!(
+            out,
+            "# HELP {} Estimated latency percentiles per route in milliseconds",
+            name
+        );
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for ((method, route), histogram) in &routes {
+            for (quantile, p) in [("0.5", 50.0), ("0.95", 95.0), ("0.99", 99.0)] {
+                let _ = writeln!(
+                    out,
+                    "{}{{method=\"{}\",route=\"{}\",quantile=\"{}\"}} {}",
+                    name,
+                    escape_label(method),
+                    escape_label(route),
+                    quantile,
+                    histogram.percentile_ms(p)
+                );
+            }
+        }
+    }
+}
+
+// One series per label set; nothing at all until the first one is recorded
+fn labelled_counter(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    series: &BTreeMap<PrometheusLabels, u64>,
+) {
+    if series.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for (labels, value) in series {
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels.render(), value);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    metric(out, "gauge", name, help, value);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    metric(out, "counter", name, help, value);
+}
+
+fn metric(out: &mut String, kind: &str, name: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_percentiles_come_from_the_buckets() {
+        let histogram: LatencyHistogram = LatencyHistogram::default();
+        assert_eq!(histogram.percentile_ms(50.0), 0.0);
+        for _ in 0..90 {
+            histogram.record(Duration::from_micros(500));
+        }
+        for _ in 0..10 {
+            histogram.record(Duration::from_millis(40));
+        }
+        assert_eq!(histogram.count(), 100);
+        assert!(histogram.percentile_ms(50.0) <= 1.0);
+        let p95: f64 = histogram.percentile_ms(95.0);
+        assert!(p95 > 25.0 && p95 <= 50.0, "{}", p95);
+
+        histogram.record(Duration::from_secs(60));
+        assert_eq!(histogram.percentile_ms(100.0), 5000.0);
+    }
+}
@@ -0,0 +1,587 @@
+use crate::error::{error_page, AppError};
+use crate::maintenance::MaintenanceStatus;
+use crate::signing::{REQUEST_ID_HEADER, SIGNATURE_HEADER};
+use crate::state::AppState;
+use actix_web::body::{to_bytes, BodySize, EitherBody, MessageBody};
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::web::Bytes;
+use actix_web::{web, App, HttpMessage, HttpResponse};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// A fresh App with every middleware of ours, innermost first. main and the tests both start
+// from here so they always run the same stack; main adds CORS on top.
+pub fn new_app() -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .wrap(from_fn(debug_delay))
+        .wrap(from_fn(read_only_guard))
+        .wrap(from_fn(html_errors))
+        .wrap(from_fn(envelope))
+        .wrap(from_fn(content_length))
+        .wrap(from_fn(sign_responses))
+        .wrap(from_fn(inject_headers))
+        .wrap(from_fn(track_requests))
+}
+
+// Count every request, its latency and whether it failed, for Metrics
+pub async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let state: Option<web::Data<AppState>> = req.app_data::<web::Data<AppState>>().cloned();
+    let started: Instant = Instant::now();
+    let res: ServiceResponse<_> = next.call(req).await?;
+    if let Some(state) = state {
+        let latency: Duration = started.elapsed();
+        let status: StatusCode = res.status();
+        state.metrics.request_finished(
+            latency,
+            status.is_client_error() || status.is_server_error(),
+        );
+        // Unrouted requests share one series rather than one per path tried
+        let route: String = res
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        state
+            .metrics
+            .record_route(res.request().method().as_str(), &route, latency);
+    }
+    Ok(res)
+}
+
+// HttpResponse::json already serialises once into a buffer; this makes the resulting
+// length explicit on every buffered response (JSON, errors, exports) so clients can rely on it
+pub async fn content_length(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let mut res: ServiceResponse<_> = next.call(req).await?;
+    let status: StatusCode = res.status();
+    let allows_body: bool = !(status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED);
+
+    if let (true, BodySize::Sized(len)) = (allows_body, res.response().body().size()) {
+        res.headers_mut()
+            .insert(CONTENT_LENGTH, HeaderValue::from(len));
+    }
+    Ok(res)
+}
+
+// Hold every request for config.debug_delay() before it is handled, so front ends can test
+// their loading states against a slow API. Does nothing outside debug mode.
+pub async fn debug_delay(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let delay: Option<Duration> = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|state| state.config.debug_delay());
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+    next.call(req).await
+}
+
+// Add the configured static headers (security policy and the like) to every response,
+// leaving any header the handler already set alone
+pub async fn inject_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let state: Option<web::Data<AppState>> = req.app_data::<web::Data<AppState>>().cloned();
+    let mut res: ServiceResponse<_> = next.call(req).await?;
+    if let Some(state) = state {
+        for (name, value) in &state.custom_headers {
+            if !res.headers().contains_key(name) {
+                res.headers_mut().insert(name.clone(), value.clone());
+            }
+        }
+    }
+    Ok(res)
+}
+
+// The id of the request being served, shared by every middleware that reports one
+#[derive(Debug, Clone)]
+struct RequestId(String);
+
+// X-Request-Id when the client sent one, else a new UUID; the same for the whole request
+fn request_id(req: &ServiceRequest) -> String {
+    if let Some(RequestId(id)) = req.extensions().get::<RequestId>() {
+        return id.clone();
+    }
+    let id: String = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(id.clone()));
+    id
+}
+
+async fn buffer(body: impl MessageBody) -> Result<Bytes, actix_web::Error> {
+    to_bytes(body).await.map_err(|err| {
+        let err: Box<dyn std::error::Error> = err.into();
+        actix_web::error::ErrorInternalServerError(err.to_string())
+    })
+}
+
+#[derive(Deserialize, Debug)]
+struct EnvelopeQuery {
+    envelope: Option<bool>,
+}
+
+// `{"data": <body>, "meta": {"request_id", "timestamp"}}` around successful JSON (and empty)
+// bodies when ?envelope=true, or config.response_envelope unless ?envelope=false. Errors keep
+// their own structured body; non-JSON bodies (CSV, NDJSON) and 204s are left as they are.
+pub async fn envelope(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let configured: bool = req
+        .app_data::<web::Data<AppState>>()
+        .is_some_and(|state| state.config.response_envelope);
+    let wanted: bool = web::Query::<EnvelopeQuery>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| query.envelope)
+        .unwrap_or(configured);
+    if !wanted {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+    let request_id: String = request_id(&req);
+
+    let res: ServiceResponse<_> = next.call(req).await?;
+    let status: StatusCode = res.status();
+    let is_json: bool = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|content_type| content_type.starts_with("application/json"));
+    if !status.is_success() || status == StatusCode::NO_CONTENT || !is_json {
+        return Ok(res.map_into_left_body());
+    }
+
+    let (http_req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let body: Bytes = buffer(body).await?;
+    let data: Value = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&body)
+            .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?
+    };
+    let wrapped: Vec<u8> = serde_json::to_vec(&json!({
+        "data": data,
+        "meta": {
+            "request_id": request_id,
+            "timestamp": Utc::now(),
+        },
+    }))
+    .map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?;
+
+    res.headers_mut().remove(CONTENT_LENGTH);
+    res.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut()
+            .insert(HeaderName::from_static("x-request-id"), value);
+    }
+    let res: HttpResponse<Bytes> = res.set_body(Bytes::from(wrapped));
+    Ok(ServiceResponse::new(http_req, res.map_into_boxed_body()).map_into_right_body())
+}
+
+// Sign the final body and status with the configured Ed25519 key, see signing.rs. The id
+// that is signed along comes from X-Request-Id, or a new one that is sent back in it.
+// Signing buffers the body, which every response here already is.
+pub async fn sign_responses(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let state: Option<web::Data<AppState>> = req.app_data::<web::Data<AppState>>().cloned();
+    let Some(state) = state.filter(|state| state.signer.is_some()) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let request_id: String = request_id(&req);
+
+    let res: ServiceResponse<_> = next.call(req).await?;
+    let (http_req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body: Bytes = buffer(body).await?;
+
+    let signature: String = state.signer.as_ref().map_or_else(String::new, |signer| {
+        signer.sign(&request_id, res.status().as_u16(), &body)
+    });
+    let mut res: HttpResponse<Bytes> = res.set_body(body);
+    // Both are visible ASCII: the id came from a header or is a UUID, the signature is base64
+    for (name, value) in [
+        (REQUEST_ID_HEADER, request_id),
+        (SIGNATURE_HEADER, signature),
+    ] {
+        let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) else {
+            continue;
+        };
+        res.headers_mut().insert(name, value);
+    }
+    Ok(ServiceResponse::new(http_req, res.map_into_boxed_body()).map_into_right_body())
+}
+
+// Swap error bodies for a small HTML page when the client prefers text/html over JSON (a
+// browser, say). Status and other headers are kept; JSON clients see no difference.
+pub async fn html_errors(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let enabled: bool = req
+        .app_data::<web::Data<AppState>>()
+        .is_some_and(|state| state.config.html_errors);
+    let wants_html: bool = enabled && prefers_html(&req);
+    let res: ServiceResponse<_> = next.call(req).await?;
+
+    let page: Option<String> = match res.response().error() {
+        Some(err) if wants_html => {
+            let code: &str = match err.as_error::<AppError>() {
+                Some(app_error) => app_error.code(),
+                None => res.status().canonical_reason().unwrap_or("error"),
+            };
+            Some(error_page(res.status(), code, &err.to_string()))
+        }
+        _ => None,
+    };
+    let Some(page) = page else {
+        return Ok(res.map_into_left_body());
+    };
+
+    let mut html: actix_web::HttpResponseBuilder = HttpResponse::build(res.status());
+    for (name, value) in res.headers() {
+        if name != CONTENT_TYPE && name != CONTENT_LENGTH {
+            html.append_header((name.clone(), value.clone()));
+        }
+    }
+    let html: HttpResponse = html.content_type("text/html; charset=utf-8").body(page);
+    Ok(res.into_response(html).map_into_right_body())
+}
+
+// text/html ranked above application/json (or JSON not asked for at all)
+fn prefers_html(req: &ServiceRequest) -> bool {
+    let Some(accept) = req.get_header::<header::Accept>() else {
+        return false;
+    };
+    accept
+        .ranked()
+        .into_iter()
+        .find(|mime| {
+            (mime.type_() == "text" && mime.subtype() == "html")
+                || (mime.type_() == "application" && mime.subtype() == "json")
+        })
+        .is_some_and(|mime| mime.subtype() == "html")
+}
+
+// Non-GET routes the guard lets through: the maintenance toggle itself (or read-only mode
+// could never be left) and POST routes that only compute over the data
+const UNGUARDED_PATHS: &[&str] = &["/admin/maintenance", "/exposure", "/validate/pair"];
+
+// Refuse writes with 503 while the server is read-only (manually or in a scheduled window)
+pub async fn read_only_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let is_write: bool = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let status: Option<MaintenanceStatus> = match req.app_data::<web::Data<AppState>>() {
+        Some(state) if is_write && !UNGUARDED_PATHS.contains(&req.path()) => {
+            Some(state.maintenance.status())
+        }
+        _ => None,
+    };
+
+    match status {
+        Some(status) if status.read_only => {
+            let message: String = "server is in read-only maintenance mode".to_string();
+            let err: AppError = match status.retry_after {
+                Some(retry_after) => AppError::RetryLater {
+                    message,
+                    retry_after,
+                },
+                None => AppError::ServiceUnavailable(message),
+            };
+            // from_error keeps the AppError on the response for html_errors
+            let resp: HttpResponse = HttpResponse::from_error(err);
+            Ok(req.into_response(resp).map_into_right_body())
+        }
+        _ => Ok(next.call(req).await?.map_into_left_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::test_support::{forex_pair, init_app, temp_database, test_state};
+    use actix_web::http::header::{self, CONTENT_LENGTH};
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use serde_json::{json, Value};
+    use std::time::{Duration, Instant};
+
+    #[actix_web::test]
+    async fn tests_content_length_matches_body() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        for (uri, status) in [
+            ("/forex_pairs", StatusCode::OK),
+            ("/rates?base=EUR&quotes=USD", StatusCode::OK),
+            ("/rates?base=EURO&quotes=USD", StatusCode::BAD_REQUEST),
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status);
+
+            let header: usize = resp
+                .headers()
+                .get(CONTENT_LENGTH)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            let body = test::read_body(resp).await;
+            assert!(!body.is_empty());
+            assert_eq!(header, body.len(), "{}", uri);
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_custom_headers_on_every_route() {
+        let mut db: Database = temp_database("custom_headers");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        db.insert(forex_pair(2, "GBP/USD", 1.27));
+        let config: Config = Config {
+            custom_headers: [
+                ("X-Frame-Options", "DENY"),
+                ("Strict-Transport-Security", "max-age=63072000"),
+                // Set by the handler, so left as it is
+                ("Content-Type", "text/html"),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+            ..Config::default()
+        };
+        let app = init_app(test_state(db, config)).await;
+
+        let created: Value = json!({ "id": 3, "pair": "USD/JPY", "price": 151.2 });
+        let updated: Value = json!({ "id": 1, "pair": "EUR/USD", "price": 1.09 });
+        // The five original routes, then a few later ones and a 404
+        for (label, req) in [
+            (
+                "POST /forex_pair",
+                test::TestRequest::post()
+                    .uri("/forex_pair")
+                    .set_json(&created),
+            ),
+            (
+                "PUT /forex_pair",
+                test::TestRequest::put()
+                    .uri("/forex_pair")
+                    .set_json(&updated),
+            ),
+            (
+                "DELETE /forex_pair/2",
+                test::TestRequest::delete().uri("/forex_pair/2"),
+            ),
+            (
+                "GET /forex_pairs",
+                test::TestRequest::get().uri("/forex_pairs"),
+            ),
+            (
+                "GET /forex_pair/1",
+                test::TestRequest::get().uri("/forex_pair/1"),
+            ),
+            (
+                "GET /forex_pair/9",
+                test::TestRequest::get().uri("/forex_pair/9"),
+            ),
+            ("GET /health", test::TestRequest::get().uri("/health")),
+            (
+                "GET /rates",
+                test::TestRequest::get().uri("/rates?base=EUR&quotes=USD"),
+            ),
+        ] {
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(
+                resp.status().is_success(),
+                label != "GET /forex_pair/9",
+                "{}",
+                label
+            );
+            assert_eq!(
+                resp.headers().get("x-frame-options").unwrap(),
+                "DENY",
+                "{}",
+                label
+            );
+            assert_eq!(
+                resp.headers()
+                    .get(header::STRICT_TRANSPORT_SECURITY)
+                    .unwrap(),
+                "max-age=63072000"
+            );
+            // PUT and DELETE answer with an empty body, which has no type of its own to keep
+            let has_body: bool = !label.starts_with("PUT") && !label.starts_with("DELETE");
+            if resp.status() == StatusCode::OK && has_body {
+                assert_eq!(
+                    resp.headers().get(header::CONTENT_TYPE).unwrap(),
+                    "application/json",
+                    "{}",
+                    label
+                );
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_errors_negotiate_json_or_html() {
+        let app = init_app(test_state(Database::new(), Config::default())).await;
+        let uri: &str = "/rates?base=%3Cb%3E&quotes=USD";
+        let request = |accept: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header((header::ACCEPT, accept))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, request("application/json")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let json: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(json["error"], "bad_request");
+
+        let resp = test::call_service(
+            &app,
+            request("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        let body = test::read_body(resp).await;
+        let html: &str = std::str::from_utf8(&body).unwrap();
+        assert!(html.contains("<h1>400 Bad Request</h1>"));
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(!html.contains("<b>"));
+
+        // JSON wins when it is ranked higher
+        let resp = test::call_service(&app, request("application/json, text/html;q=0.5")).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+    }
+
+    #[actix_web::test]
+    async fn tests_envelope_wraps_successful_json_on_request() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let app = init_app(test_state(db, Config::default())).await;
+
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let raw: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(raw["pair"], "EUR/USD");
+        assert!(raw.get("data").is_none());
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/1?envelope=true")
+            .insert_header(("X-Request-Id", "abc-123"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let declared: usize = resp
+            .headers()
+            .get(CONTENT_LENGTH)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let body = test::read_body(resp).await;
+        assert_eq!(declared, body.len());
+        let wrapped: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(wrapped["data"], raw);
+        assert_eq!(wrapped["meta"]["request_id"], "abc-123");
+        assert!(wrapped["meta"]["timestamp"].is_string());
+
+        // Errors keep their structured body
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/9?envelope=true")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // On by configuration, off again per request
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let config: Config = Config {
+            response_envelope: true,
+            ..Config::default()
+        };
+        let app = init_app(test_state(db, config)).await;
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["pair"], "EUR/USD");
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/1?envelope=false")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["pair"], "EUR/USD");
+    }
+
+    #[actix_web::test]
+    async fn tests_debug_delay_slows_responses_in_debug_mode_only() {
+        let time_request = |config: Config| async move {
+            let app = init_app(test_state(Database::new(), config)).await;
+            let started: Instant = Instant::now();
+            let req = test::TestRequest::get().uri("/forex_pairs").to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            started.elapsed()
+        };
+
+        let delayed: Duration = time_request(Config {
+            debug_mode: true,
+            debug_delay_ms: Some(200),
+            ..Config::default()
+        })
+        .await;
+        let undelayed: Duration = time_request(Config::default()).await;
+        // Without debug mode the setting is ignored
+        let ignored: Duration = time_request(Config {
+            debug_delay_ms: Some(200),
+            ..Config::default()
+        })
+        .await;
+        assert!(delayed >= Duration::from_millis(200));
+        assert!(undelayed < Duration::from_millis(200));
+        assert!(ignored < Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::error::AppError;
+use std::path::{Component, Path, PathBuf};
+
+// Directory the database file lives in; admin routes may only touch files in here
+pub fn data_dir(database_path: &Path) -> &Path {
+    match database_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+// Resolve a file path taken from an admin request against dir. Relative paths are taken
+// relative to dir, absolute ones must already point into it. `..` is refused outright, and
+// the existing part of the path is canonicalized so a symlink cannot lead out either. The
+// file itself need not exist, its directory must. Every admin route that takes a path goes
+// through here.
+pub fn resolve_in_dir(dir: &Path, requested: &Path) -> Result<PathBuf, AppError> {
+    let escapes = || {
+        AppError::BadRequest(format!(
+            "{} is outside the data directory",
+            requested.display()
+        ))
+    };
+    if requested
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Err(escapes());
+    }
+    let Some(file_name) = requested.file_name() else {
+        return Err(AppError::BadRequest(format!(
+            "{} does not name a file",
+            requested.display()
+        )));
+    };
+
+    let root: PathBuf = dir.canonicalize().map_err(|err| {
+        AppError::Internal(format!(
+            "data directory {} is not usable: {}",
+            dir.display(),
+            err
+        ))
+    })?;
+    let joined: PathBuf = root.join(requested);
+    let parent: PathBuf = joined
+        .parent()
+        .unwrap_or(&root)
+        .canonicalize()
+        .map_err(|err| {
+            AppError::BadRequest(format!(
+                "directory of {} is not usable: {}",
+                requested.display(),
+                err
+            ))
+        })?;
+    let resolved: PathBuf = match parent.join(file_name).canonicalize() {
+        Ok(existing) => existing,
+        Err(_) => parent.join(file_name),
+    };
+    if resolved == root || !resolved.starts_with(&root) {
+        return Err(escapes());
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn tests_resolve_in_dir_accepts_paths_inside() {
+        let dir: PathBuf = temp_dir("resolve_inside");
+        fs::create_dir_all(dir.join("backups")).unwrap();
+        let root: PathBuf = dir.canonicalize().unwrap();
+
+        assert_eq!(
+            resolve_in_dir(&dir, Path::new("moved.json")).unwrap(),
+            root.join("moved.json")
+        );
+        assert_eq!(
+            resolve_in_dir(&dir, Path::new("./backups/old.json")).unwrap(),
+            root.join("backups").join("old.json")
+        );
+        assert_eq!(
+            resolve_in_dir(&dir, &dir.join("moved.json")).unwrap(),
+            root.join("moved.json")
+        );
+    }
+
+    #[test]
+    fn tests_resolve_in_dir_blocks_traversal() {
+        let dir: PathBuf = temp_dir("resolve_traversal");
+        fs::create_dir_all(dir.join("backups")).unwrap();
+        let outside: PathBuf = temp_dir("resolve_outside");
+        std::os::unix::fs::symlink(&outside, dir.join("link")).unwrap();
+
+        for requested in [
+            "../database.json",
+            "backups/../../database.json",
+            "backups/../database.json",
+            "/etc/passwd",
+            "/tmp/database.json",
+            "link/database.json",
+            ".",
+            "",
+        ] {
+            let result: Result<PathBuf, AppError> = resolve_in_dir(&dir, Path::new(requested));
+            assert!(matches!(result, Err(AppError::BadRequest(_))));
+        }
+        assert!(matches!(
+            resolve_in_dir(&dir, &outside.join("database.json")),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const DEFAULT_SAVE_MAX_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+
+// Serialised database taken under the data lock, written after it is released
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+    // Order in which snapshots were taken, so a stale one never overwrites a newer save
+    pub generation: u64,
+}
+
+impl Snapshot {
+    pub fn write(&self) -> io::Result<()> {
+        fs::write(&self.path, &self.data)
+    }
+
+    pub async fn write_with_retry(&self, max_attempts: u32) -> io::Result<()> {
+        retry_with_backoff(max_attempts, || async { self.write() }).await
+    }
+}
+
+// Run op up to max_attempts times, sleeping 10ms, 20ms, 40ms, ... between attempts.
+// Only the last error is returned.
+pub async fn retry_with_backoff<T, F, Fut>(max_attempts: u32, mut op: F) -> io::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+{
+    let max_attempts: u32 = max_attempts.max(1);
+    let mut backoff: Duration = INITIAL_BACKOFF;
+    let mut attempt: u32 = 1;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < max_attempts => {
+                tracing::warn!(attempt, max_attempts, error = %err, "save failed, retrying");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::time::Instant;
+
+    // Stand-in writer failing the first `failures` calls
+    struct FlakyWriter {
+        failures: u32,
+        calls: Cell<u32>,
+    }
+
+    impl FlakyWriter {
+        fn write(&self) -> io::Result<()> {
+            self.calls.set(self.calls.get() + 1);
+            if self.calls.get() <= self.failures {
+                Err(io::Error::other("stale NFS handle"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn tests_retry_succeeds_on_third_attempt() {
+        let writer: FlakyWriter = FlakyWriter {
+            failures: 2,
+            calls: Cell::new(0),
+        };
+        let started: Instant = Instant::now();
+        retry_with_backoff(3, || async { writer.write() })
+            .await
+            .unwrap();
+        assert_eq!(writer.calls.get(), 3);
+        // 10ms then 20ms of backoff
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn tests_retry_gives_up_after_max_attempts() {
+        let writer: FlakyWriter = FlakyWriter {
+            failures: 5,
+            calls: Cell::new(0),
+        };
+        let err: io::Error = retry_with_backoff(2, || async { writer.write() })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "stale NFS handle");
+        assert_eq!(writer.calls.get(), 2);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::breaker::MAX_RETRY_AFTER;
+use async_trait::async_trait;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client as HttpClient, StatusCode};
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderError {
+    // 429 from upstream, with its Retry-After if it sent one
+    RateLimited { retry_after: Option<Duration> },
+    Unavailable(String),
+    InvalidResponse(String),
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RateLimited { .. } => write!(f, "price provider is rate limiting requests"),
+            Self::Unavailable(msg) => write!(f, "price provider unavailable: {}", msg),
+            Self::InvalidResponse(msg) => write!(f, "invalid price provider response: {}", msg),
+        }
+    }
+}
+
+impl ProviderError {
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+// A price together with the upstream body it was parsed from, when the provider has one
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderQuote {
+    pub price: f64,
+    pub raw: Option<String>,
+}
+
+// Source of live prices for POST /forex_pair/{id}/refresh
+#[async_trait]
+pub trait PriceProvider: Send + Sync {
+    async fn fetch_price(&self, pair: &str) -> Result<f64, ProviderError>;
+
+    // Providers that see a response body override this so refresh can show it when debugging
+    async fn fetch_quote(&self, pair: &str) -> Result<ProviderQuote, ProviderError> {
+        Ok(ProviderQuote {
+            price: self.fetch_price(pair).await?,
+            raw: None,
+        })
+    }
+}
+
+// GET {base_url}?pair=EUR/USD answering `{"price": 1.0842}`
+pub struct HttpPriceProvider {
+    client: HttpClient,
+    base_url: String,
+}
+
+#[derive(Deserialize)]
+struct PriceBody {
+    price: f64,
+}
+
+impl HttpPriceProvider {
+    // client is cheap to clone and shares its connection pool, pass AppState::http_client
+    pub fn new(base_url: impl Into<String>, client: HttpClient) -> Self {
+        Self {
+            client,
+            base_url: base_url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceProvider for HttpPriceProvider {
+    async fn fetch_price(&self, pair: &str) -> Result<f64, ProviderError> {
+        self.fetch_quote(pair).await.map(|quote| quote.price)
+    }
+
+    async fn fetch_quote(&self, pair: &str) -> Result<ProviderQuote, ProviderError> {
+        let resp: reqwest::Response = self
+            .client
+            .get(&self.base_url)
+            .query(&[("pair", pair)])
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| ProviderError::Unavailable(e.to_string()))?;
+
+        match resp.status() {
+            StatusCode::TOO_MANY_REQUESTS => Err(ProviderError::RateLimited {
+                retry_after: resp
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_retry_after),
+            }),
+            status if !status.is_success() => {
+                Err(ProviderError::Unavailable(format!("HTTP {}", status)))
+            }
+            _ => {
+                let raw: String = resp
+                    .text()
+                    .await
+                    .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+                let body: PriceBody = serde_json::from_str(&raw)
+                    .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+                Ok(ProviderQuote {
+                    price: body.price,
+                    raw: Some(raw),
+                })
+            }
+        }
+    }
+}
+
+// Retry-After in delta-seconds, capped at MAX_RETRY_AFTER; the HTTP-date form is rare for rate
+// limits and ignored
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|secs| Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tests_parse_retry_after() {
+        assert_eq!(parse_retry_after(" 120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2026 07:28:00 GMT"), None);
+        assert_eq!(
+            parse_retry_after("18446744073709551615"),
+            Some(MAX_RETRY_AFTER)
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::config::Config;
+use crate::handlers::admin::{
+    audit_log, debug_state, maintenance_status, reindex, reload, relocate_database, set_maintenance,
+};
+use crate::handlers::exposure::exposure;
+use crate::handlers::forex_pair::{
+    clear_forex_pair_history, create_forex_pair, delete_forex_pair, ensure_forex_pair,
+    patch_forex_pair, read_all_forex_pairs, read_checksum, read_dependencies, read_forex_pair,
+    read_forex_pair_by_pair, read_neighbors, read_oldest, rebalance_forex_pairs, recalculate_ids,
+    rename_forex_pair, update_forex_pair,
+};
+use crate::handlers::health::health;
+use crate::handlers::import_export::{
+    dump, export_csv, export_json, export_ndjson, import_json, restore, sort_by_price,
+    IMPORT_PAYLOAD_LIMIT,
+};
+use crate::handlers::ingest::ingest;
+use crate::handlers::metrics::metrics;
+use crate::handlers::provider::refresh_forex_pair;
+use crate::handlers::rates::read_rates;
+use crate::handlers::simulation::{random_walk, seed_database};
+use crate::handlers::stats::{heatmap, percentile};
+use crate::handlers::validate::validate_pair;
+use crate::handlers::wait::wait_for_price;
+use crate::handlers::watchlist::{add_to_watchlist, read_watchlist, remove_from_watchlist};
+use actix_web::web;
+
+pub fn configure(cfg: &mut web::ServiceConfig, config: &Config) {
+    cfg.route("/forex_pair", web::post().to(create_forex_pair))
+        .route("/forex_pairs", web::get().to(read_all_forex_pairs))
+        .route("/forex_pairs/percentile", web::get().to(percentile))
+        .route("/forex_pairs/heatmap", web::get().to(heatmap))
+        .route("/forex_pairs/oldest", web::get().to(read_oldest))
+        .route("/forex_pairs/checksum", web::get().to(read_checksum))
+        .route("/forex_pairs/random_walk", web::get().to(random_walk))
+        .route("/forex_pairs/rename", web::post().to(rename_forex_pair))
+        .route("/forex_pairs/ensure", web::put().to(ensure_forex_pair))
+        .route(
+            "/forex_pairs/by_pair/{pair:.+}",
+            web::get().to(read_forex_pair_by_pair),
+        )
+        .route("/forex_pair", web::put().to(update_forex_pair))
+        .route("/forex_pair/{id}", web::get().to(read_forex_pair))
+        .route("/forex_pair/{id}", web::patch().to(patch_forex_pair))
+        .route("/forex_pair/{id}/wait", web::get().to(wait_for_price))
+        .route("/forex_pair/{id}/neighbors", web::get().to(read_neighbors))
+        .route(
+            "/forex_pair/{id}/dependencies",
+            web::get().to(read_dependencies),
+        )
+        .route(
+            "/forex_pair/{id}/refresh",
+            web::post().to(refresh_forex_pair),
+        )
+        .route("/forex_pairs/export/json", web::get().to(export_json))
+        .route("/forex_pairs/export/csv", web::get().to(export_csv))
+        .route("/forex_pairs/export/ndjson", web::get().to(export_ndjson))
+        .route("/rates", web::get().to(read_rates))
+        .route("/exposure", web::post().to(exposure))
+        .route("/ingest", web::post().to(ingest))
+        .route("/validate/pair", web::post().to(validate_pair))
+        .route("/health", web::get().to(health))
+        .route("/metrics", web::get().to(metrics))
+        // Pairs contain a slash, so the last segment takes the rest of the path
+        .route("/users/{user_id}/watchlist", web::get().to(read_watchlist))
+        .route(
+            "/users/{user_id}/watchlist/{pair:.+}",
+            web::post().to(add_to_watchlist),
+        )
+        .route(
+            "/users/{user_id}/watchlist/{pair:.+}",
+            web::delete().to(remove_from_watchlist),
+        );
+
+    // Destructive routes
+    if config.enable_delete {
+        cfg.route("/forex_pair/{id}", web::delete().to(delete_forex_pair));
+    }
+
+    // Admin routes
+    if config.enable_admin {
+        cfg.route("/admin/debug/state", web::get().to(debug_state))
+            .route("/admin/audit", web::get().to(audit_log))
+            .route("/admin/maintenance", web::get().to(maintenance_status))
+            .route("/admin/maintenance", web::post().to(set_maintenance))
+            .service(
+                web::resource("/forex_pairs/import/json")
+                    .app_data(web::PayloadConfig::new(IMPORT_PAYLOAD_LIMIT))
+                    .route(web::post().to(import_json)),
+            )
+            .route("/forex_pairs/sort_by_price", web::post().to(sort_by_price))
+            .route("/forex_pairs/seed", web::post().to(seed_database))
+            .route(
+                "/forex_pairs/rebalance",
+                web::post().to(rebalance_forex_pairs),
+            )
+            .route(
+                "/forex_pairs/recalculate_ids",
+                web::post().to(recalculate_ids),
+            )
+            .route(
+                "/forex_pair/{id}/history",
+                web::delete().to(clear_forex_pair_history),
+            )
+            .route("/admin/dump", web::get().to(dump))
+            .route("/admin/reload", web::post().to(reload))
+            .route("/admin/reindex", web::post().to(reindex))
+            .route(
+                "/admin/relocate_database",
+                web::post().to(relocate_database),
+            )
+            .service(
+                web::resource("/admin/restore")
+                    .app_data(web::PayloadConfig::new(IMPORT_PAYLOAD_LIMIT))
+                    .route(web::post().to(restore)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::test_support::{admin_config, admin_header, forex_pair, init_app, test_state};
+    use actix_web::{http::StatusCode, test};
+
+    #[actix_web::test]
+    async fn tests_disabled_routes_are_not_registered() {
+        let mut db: Database = Database::new();
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let config: Config = Config {
+            enable_delete: false,
+            enable_admin: false,
+            ..admin_config()
+        };
+        let app = init_app(test_state(db, config)).await;
+
+        let req = test::TestRequest::delete()
+            .uri("/forex_pair/1")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/debug/state")
+            .insert_header(admin_header())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // Everything else keeps working
+        let req = test::TestRequest::get().uri("/forex_pair/1").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
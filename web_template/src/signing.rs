@@ -0,0 +1,170 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::path::Path;
+
+pub const SIGNATURE_HEADER: &str = "X-Response-Signature";
+// Echoed back (or generated) on every signed response; it is part of what gets signed
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Signs response bodies with the Ed25519 key from Config::response_signing_key
+pub struct ResponseSigner {
+    key: SigningKey,
+}
+
+impl ResponseSigner {
+    pub fn new(key: SigningKey) -> Self {
+        Self { key }
+    }
+
+    // A PKCS#8 PEM file, as written by `openssl genpkey -algorithm ed25519`
+    pub fn from_pem_file(path: &Path) -> Result<Self, String> {
+        let pem: String = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        let key: SigningKey = SigningKey::from_pkcs8_pem(&pem)
+            .map_err(|err| format!("{} is not an Ed25519 private key: {}", path.display(), err))?;
+        Ok(Self::new(key))
+    }
+
+    // Hand this to clients so they can verify
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.key.verifying_key()
+    }
+
+    // Base64 of the signature over signing_payload
+    pub fn sign(&self, request_id: &str, status: u16, body: &[u8]) -> String {
+        let signature: Signature = self.key.sign(&signing_payload(request_id, status, body));
+        BASE64.encode(signature.to_bytes())
+    }
+}
+
+// `<request_id>|<status>|<body>`, the body as raw bytes
+pub fn signing_payload(request_id: &str, status: u16, body: &[u8]) -> Vec<u8> {
+    let mut payload: Vec<u8> = format!("{}|{}|", request_id, status).into_bytes();
+    payload.extend_from_slice(body);
+    payload
+}
+
+// What a client needs from a response to check its signature
+#[derive(Debug, Clone)]
+pub struct SignedResponse<'a> {
+    // The X-Request-Id response header
+    pub request_id: &'a str,
+    pub status: u16,
+    pub body: &'a [u8],
+    // The X-Response-Signature response header
+    pub signature: &'a str,
+}
+
+// False for a malformed signature as well as for a body, status or id that was changed
+pub fn verify_response_signature(public_key: &VerifyingKey, response: &SignedResponse) -> bool {
+    let Ok(bytes) = BASE64.decode(response.signature) else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_slice(&bytes) else {
+        return false;
+    };
+    public_key
+        .verify(
+            &signing_payload(response.request_id, response.status, response.body),
+            &signature,
+        )
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{forex_pair, init_app, temp_database, temp_dir, test_state};
+    use actix_web::test;
+    use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+    use ed25519_dalek::pkcs8::EncodePrivateKey;
+    use std::path::PathBuf;
+
+    fn key_file(name: &str) -> (PathBuf, VerifyingKey) {
+        let key: SigningKey = SigningKey::from_bytes(&[7; 32]);
+        let path: PathBuf = temp_dir(name).join("signing_key.pem");
+        std::fs::write(&path, key.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes()).unwrap();
+        (path, key.verifying_key())
+    }
+
+    // `test` here is actix_web::test, hence the async unit test
+    #[actix_web::test]
+    async fn tests_signed_responses_verify_and_tampering_is_caught() {
+        let (path, public_key): (PathBuf, VerifyingKey) = key_file("signing");
+        let mut db = temp_database("signing");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let config: Config = Config {
+            response_signing_key: Some(path),
+            ..Config::default()
+        };
+        let state = test_state(db, config);
+        assert_eq!(state.signer.as_ref().unwrap().verifying_key(), public_key);
+        let app = init_app(state).await;
+
+        let req = test::TestRequest::get()
+            .uri("/forex_pair/1")
+            .insert_header((REQUEST_ID_HEADER, "req-42"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let status: u16 = resp.status().as_u16();
+        let request_id: String = resp
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let signature: String = resp
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = test::read_body(resp).await;
+        assert_eq!(request_id, "req-42");
+
+        let signed: SignedResponse = SignedResponse {
+            request_id: &request_id,
+            status,
+            body: &body,
+            signature: &signature,
+        };
+        assert!(verify_response_signature(&public_key, &signed));
+
+        let tampered: Vec<u8> = String::from_utf8(body.to_vec())
+            .unwrap()
+            .replace("1.08", "1.18")
+            .into_bytes();
+        assert!(!verify_response_signature(
+            &public_key,
+            &SignedResponse {
+                body: &tampered,
+                ..signed.clone()
+            }
+        ));
+        assert!(!verify_response_signature(
+            &public_key,
+            &SignedResponse {
+                status: 500,
+                ..signed.clone()
+            }
+        ));
+        assert!(!verify_response_signature(
+            &public_key,
+            &SignedResponse {
+                signature: "not base64",
+                ..signed
+            }
+        ));
+
+        // Without an incoming id one is generated and sent back
+        let req = test::TestRequest::get().uri("/forex_pair/9").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.headers().contains_key(REQUEST_ID_HEADER));
+        assert!(resp.headers().contains_key(SIGNATURE_HEADER));
+    }
+}
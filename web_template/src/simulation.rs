@@ -0,0 +1,285 @@
+use crate::database::{ForexPair, PricePoint};
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_DRIFT: f64 = 0.0;
+pub const DEFAULT_VOLATILITY: f64 = 0.01;
+pub const DEFAULT_STEP_SECS: i64 = 60;
+
+// Geometric Brownian motion, with drift and volatility expressed per step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RandomWalk {
+    pub drift: f64,
+    pub volatility: f64,
+    pub step: Duration,
+}
+
+impl Default for RandomWalk {
+    fn default() -> Self {
+        Self {
+            drift: DEFAULT_DRIFT,
+            volatility: DEFAULT_VOLATILITY,
+            step: Duration::seconds(DEFAULT_STEP_SECS),
+        }
+    }
+}
+
+impl RandomWalk {
+    // `steps` points after `start_price`, one step apart from `start`. The same seed always
+    // yields the same prices: S' = S * exp((drift - volatility^2 / 2) + volatility * Z).
+    // The series ends early rather than overflow if a timestamp would leave chrono's range.
+    pub fn generate(
+        &self,
+        start_price: f64,
+        start: DateTime<Utc>,
+        steps: usize,
+        seed: u64,
+    ) -> Vec<PricePoint> {
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        let growth: f64 = self.drift - self.volatility * self.volatility / 2.0;
+        let mut price: f64 = start_price;
+        (1..=steps)
+            .map_while(|step| {
+                let offset: Duration = self.step.checked_mul(i32::try_from(step).ok()?)?;
+                let timestamp: DateTime<Utc> = start.checked_add_signed(offset)?;
+                price *= (growth + self.volatility * standard_normal(&mut rng)).exp();
+                Some(PricePoint { timestamp, price })
+            })
+            .collect()
+    }
+}
+
+// Pairs synthetic_pairs draws from, with a typical price to vary around
+pub const SEED_PAIRS: &[(&str, f64)] = &[
+    ("EUR/USD", 1.085),
+    ("GBP/USD", 1.27),
+    ("USD/JPY", 151.2),
+    ("USD/CHF", 0.905),
+    ("AUD/USD", 0.66),
+    ("USD/CAD", 1.36),
+    ("NZD/USD", 0.61),
+    ("EUR/GBP", 0.855),
+    ("EUR/JPY", 164.0),
+    ("GBP/JPY", 192.0),
+    ("EUR/CHF", 0.98),
+    ("AUD/JPY", 99.8),
+    ("EUR/AUD", 1.64),
+    ("EUR/CAD", 1.475),
+    ("GBP/CHF", 1.15),
+    ("CHF/JPY", 167.0),
+    ("AUD/NZD", 1.085),
+    ("CAD/JPY", 111.2),
+    ("GBP/AUD", 1.92),
+    ("EUR/NZD", 1.78),
+    ("USD/SGD", 1.345),
+    ("USD/HKD", 7.82),
+    ("USD/MXN", 17.1),
+    ("USD/ZAR", 18.6),
+    ("USD/SEK", 10.45),
+    ("USD/NOK", 10.6),
+    ("USD/CNH", 7.24),
+    ("USD/TRY", 32.2),
+    ("USD/PLN", 3.98),
+    ("USD/INR", 83.3),
+];
+
+// USD value of every currency in SEED_PAIRS, from the pairs quoted against USD
+fn usd_values() -> BTreeMap<&'static str, f64> {
+    let mut values: BTreeMap<&'static str, f64> = BTreeMap::from([("USD", 1.0)]);
+    for (pair, typical) in SEED_PAIRS {
+        match pair.split_once('/') {
+            Some((base, "USD")) => values.insert(base, *typical),
+            Some(("USD", quote)) => values.insert(quote, 1.0 / typical),
+            _ => None,
+        };
+    }
+    values
+}
+
+// Every other ordering of two SEED_PAIRS currencies (USD/EUR, CHF/SEK, ...), in pair order,
+// with a typical price implied by the USD values
+fn cross_pairs() -> Vec<(String, f64)> {
+    let values: BTreeMap<&'static str, f64> = usd_values();
+    let mut crosses: Vec<(String, f64)> = Vec::new();
+    for (base, base_value) in &values {
+        for (quote, quote_value) in &values {
+            let pair: String = format!("{}/{}", base, quote);
+            if base != quote && !SEED_PAIRS.iter().any(|(seed_pair, _)| *seed_pair == pair) {
+                crosses.push((pair, base_value / quote_value));
+            }
+        }
+    }
+    crosses
+}
+
+// How many distinct pairs synthetic_pairs can make: SEED_PAIRS plus their crosses
+pub fn max_synthetic_pairs() -> usize {
+    let currencies: usize = usd_values().len();
+    currencies * (currencies - 1)
+}
+
+// count distinct pairs with ids 1..=count, priced within 5% of their typical price and quoted
+// with a small spread. Drawn from SEED_PAIRS, and once those run out from the other crosses
+// of their currencies. The same seed always yields the same records. None when count is
+// larger than max_synthetic_pairs().
+pub fn synthetic_pairs(count: usize, seed: u64) -> Option<Vec<ForexPair>> {
+    if count > max_synthetic_pairs() {
+        return None;
+    }
+    let mut rng: StdRng = StdRng::seed_from_u64(seed);
+    let mut chosen: Vec<(String, f64)> = SEED_PAIRS
+        .choose_multiple(&mut rng, count.min(SEED_PAIRS.len()))
+        .map(|(pair, typical)| (pair.to_string(), *typical))
+        .collect();
+    if count > SEED_PAIRS.len() {
+        let crosses: Vec<(String, f64)> = cross_pairs();
+        chosen.extend(
+            crosses
+                .choose_multiple(&mut rng, count - SEED_PAIRS.len())
+                .cloned(),
+        );
+    }
+    let forex_pairs: Vec<ForexPair> = chosen
+        .into_iter()
+        .zip(1..)
+        .map(|((pair, typical), id)| {
+            // Quoted to the pipette: 5 decimals, 3 for prices of 10 and up, 7 for the tiny
+            // crosses such as JPY/GBP
+            let decimals: i32 = match typical {
+                typical if typical >= 10.0 => 3,
+                typical if typical >= 0.1 => 5,
+                _ => 7,
+            };
+            let scale: f64 = 10f64.powi(decimals);
+            let round = |price: f64| (price * scale).round() / scale;
+            let price: f64 = round(typical * (1.0 + rng.gen_range(-0.05..0.05)));
+            let half_spread: f64 = rng.gen_range(2..=15) as f64 / scale;
+            ForexPair {
+                id,
+                pair,
+                price,
+                bid: Some(round(price - half_spread)),
+                ask: Some(round(price + half_spread)),
+                created_at: None,
+                extra_fields: None,
+            }
+        })
+        .collect();
+    Some(forex_pairs)
+}
+
+// Box-Muller transform; 1 - gen() keeps the logarithm's argument in (0, 1]
+fn standard_normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen::<f64>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::DEFAULT_MAX_SPREAD_PCT;
+
+    #[test]
+    fn tests_random_walk_is_deterministic_per_seed() {
+        let walk: RandomWalk = RandomWalk::default();
+        let start: DateTime<Utc> = Utc::now();
+        let first: Vec<PricePoint> = walk.generate(1.08, start, 100, 7);
+        assert_eq!(first, walk.generate(1.08, start, 100, 7));
+        assert_ne!(first, walk.generate(1.08, start, 100, 8));
+
+        assert_eq!(first.len(), 100);
+        assert!(first.iter().all(|point| point.price > 0.0));
+        assert_eq!(first[1].timestamp - first[0].timestamp, walk.step);
+
+        let flat: RandomWalk = RandomWalk {
+            volatility: 0.0,
+            ..RandomWalk::default()
+        };
+        assert!(flat
+            .generate(1.08, start, 5, 1)
+            .iter()
+            .all(|point| point.price == 1.08));
+    }
+
+    #[test]
+    fn tests_random_walk_stops_before_timestamps_overflow() {
+        let walk: RandomWalk = RandomWalk {
+            step: Duration::days(365_000),
+            ..RandomWalk::default()
+        };
+        let points: Vec<PricePoint> = walk.generate(1.08, Utc::now(), 1_000, 7);
+        assert!(!points.is_empty() && points.len() < 1_000);
+    }
+
+    #[test]
+    fn tests_synthetic_pairs_are_deterministic_and_distinct() {
+        let first: Vec<ForexPair> = synthetic_pairs(10, 42).unwrap();
+        let again: Vec<ForexPair> = synthetic_pairs(10, 42).unwrap();
+        let fields = |forex_pairs: &[ForexPair]| -> Vec<(u64, String, f64)> {
+            forex_pairs
+                .iter()
+                .map(|forex_pair| (forex_pair.id, forex_pair.pair.clone(), forex_pair.price))
+                .collect()
+        };
+        assert_eq!(fields(&first), fields(&again));
+        assert_ne!(fields(&first), fields(&synthetic_pairs(10, 43).unwrap()));
+
+        let pairs: std::collections::HashSet<&str> = first
+            .iter()
+            .map(|forex_pair| forex_pair.pair.as_str())
+            .collect();
+        assert_eq!(pairs.len(), 10);
+        assert!(first
+            .iter()
+            .all(|forex_pair| forex_pair.bid < Some(forex_pair.price)
+                && Some(forex_pair.price) < forex_pair.ask));
+        assert_eq!(synthetic_pairs(SEED_PAIRS.len(), 1).unwrap().len(), 30);
+    }
+
+    #[test]
+    fn tests_synthetic_pairs_beyond_the_built_in_list() {
+        let max: usize = max_synthetic_pairs();
+        // 18 currencies in SEED_PAIRS, each quoted against the 17 others
+        assert_eq!(max, 18 * 17);
+
+        let first: Vec<ForexPair> = synthetic_pairs(100, 42).unwrap();
+        assert_eq!(first.len(), 100);
+        assert_eq!(first[99].id, 100);
+        let again: Vec<ForexPair> = synthetic_pairs(100, 42).unwrap();
+        assert!(first
+            .iter()
+            .zip(&again)
+            .all(|(a, b)| a.pair == b.pair && a.price == b.price));
+
+        let all: Vec<ForexPair> = synthetic_pairs(max, 1).unwrap();
+        let pairs: std::collections::HashSet<&str> = all
+            .iter()
+            .map(|forex_pair| forex_pair.pair.as_str())
+            .collect();
+        assert_eq!(pairs.len(), max);
+        for forex_pair in &all {
+            assert_eq!(
+                ForexPair::normalize_pair(&forex_pair.pair),
+                Ok(forex_pair.pair.clone())
+            );
+            assert!(
+                forex_pair.validate(DEFAULT_MAX_SPREAD_PCT).is_ok(),
+                "{:?}",
+                forex_pair
+            );
+        }
+        // Crosses are priced from the USD legs: CHF/JPY is built in, JPY/CHF is not
+        let jpy_chf: &ForexPair = all.iter().find(|p| p.pair == "JPY/CHF").unwrap();
+        let typical: f64 = 0.905 / 151.2;
+        assert!(
+            (jpy_chf.price / typical - 1.0).abs() <= 0.05,
+            "{:?}",
+            jpy_chf
+        );
+        assert!(synthetic_pairs(max + 1, 1).is_none());
+    }
+}
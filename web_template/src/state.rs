@@ -0,0 +1,422 @@
+use crate::audit::AuditLog;
+use crate::breaker::CircuitBreaker;
+use crate::config::Config;
+use crate::database::{Database, RepairReport};
+use crate::error::AppError;
+use crate::events::{ForexPairEvent, ForexPairEventData};
+use crate::maintenance::Maintenance;
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::persistence::Snapshot;
+use crate::provider::{HttpPriceProvider, PriceProvider};
+use crate::signing::ResponseSigner;
+use crate::webhook;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::web;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Semaphore, SemaphorePermit};
+
+// Events a slow subscriber may fall behind by before it sees RecvError::Lagged
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+pub struct AppState {
+    pub db: Mutex<Database>,
+    pub config: Config,
+    pub audit: AuditLog,
+    pub maintenance: Maintenance,
+    // Bounds how many mutating handlers may queue on the data lock at once
+    pub write_semaphore: Semaphore,
+    pub metrics: Metrics,
+    // Every published event, for in-process subscribers such as the long-poll waiters
+    pub events: broadcast::Sender<ForexPairEvent>,
+    pub provider: Option<Arc<dyn PriceProvider>>,
+    pub provider_breaker: CircuitBreaker,
+    // config.custom_headers parsed once, for the inject_headers middleware
+    pub custom_headers: Vec<(HeaderName, HeaderValue)>,
+    // Shared by every outbound call (price provider, webhook) so they share one pool
+    pub http_client: reqwest::Client,
+    // From config.response_signing_key, for the sign_responses middleware
+    pub signer: Option<ResponseSigner>,
+    started_at: Instant,
+    snapshot_generation: AtomicU64,
+    // The persistence lock: serialises every file operation (saves and reloads) so two saves
+    // never interleave and a load never reads a half-written file. Holds the generation of the
+    // snapshot last written to disk. Lock ordering: take it before the data lock, never while
+    // holding it (db is a std Mutex, which must not be held across the await anyway).
+    file_lock: tokio::sync::Mutex<u64>,
+}
+
+impl AppState {
+    pub fn new(mut db: Database, config: Config) -> Self {
+        db.set_id_strategy(config.id_strategy);
+        if config.repair_on_load {
+            repair_loaded(&mut db);
+        }
+        if let Some(delay) = config.debug_delay() {
+            tracing::warn!(
+                delay_ms = delay.as_millis() as u64,
+                "debug mode: delaying every request"
+            );
+        }
+        let http_client: reqwest::Client = reqwest::Client::new();
+        let provider: Option<Arc<dyn PriceProvider>> = config.provider_url.as_ref().map(|url| {
+            Arc::new(HttpPriceProvider::new(url, http_client.clone())) as Arc<dyn PriceProvider>
+        });
+        let custom_headers: Vec<(HeaderName, HeaderValue)> =
+            parse_custom_headers(&config.custom_headers)
+                .unwrap_or_else(|err| panic!("invalid custom header: {}", err));
+        let signer: Option<ResponseSigner> = config.response_signing_key.as_ref().map(|path| {
+            ResponseSigner::from_pem_file(path)
+                .unwrap_or_else(|err| panic!("invalid response signing key: {}", err))
+        });
+        Self {
+            db: Mutex::new(db),
+            signer,
+            custom_headers,
+            maintenance: Maintenance::new(config.maintenance_windows.clone()),
+            write_semaphore: Semaphore::new(config.write_concurrency),
+            provider,
+            http_client,
+            started_at: Instant::now(),
+            provider_breaker: CircuitBreaker::new(
+                config.breaker_failure_threshold,
+                config.breaker_cooldown,
+            ),
+            config,
+            audit: AuditLog::default(),
+            metrics: Metrics::default(),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            snapshot_generation: AtomicU64::new(0),
+            file_lock: tokio::sync::Mutex::new(0),
+        }
+    }
+
+    // Take while holding the data lock so generations follow the order of the changes
+    pub fn snapshot(&self, db: &Database) -> Result<Snapshot, AppError> {
+        let mut snapshot: Snapshot = db.snapshot()?;
+        snapshot.generation = self.snapshot_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(snapshot)
+    }
+
+    // Write a snapshot (with retries) after the data lock has been released. A snapshot
+    // older than the one already on disk is dropped: the newer file includes its changes.
+    pub async fn persist(&self, snapshot: Snapshot) -> Result<(), AppError> {
+        let mut persisted: tokio::sync::MutexGuard<u64> = self.file_lock.lock().await;
+        if snapshot.generation <= *persisted {
+            return Ok(());
+        }
+        snapshot
+            .write_with_retry(self.config.save_max_attempts)
+            .await?;
+        *persisted = snapshot.generation;
+        self.metrics.record_save(snapshot.generation);
+        Ok(())
+    }
+
+    // Replace the live data with the database file, under the persistence lock. True when
+    // there was no file and the live database is now empty. An unreadable file is refused so
+    // the live data (and, on the next save, the file) is not replaced by an empty database.
+    // The file is read off the async worker and the data lock is only taken for the swap.
+    pub async fn reload(&self) -> Result<bool, AppError> {
+        let mut persisted: tokio::sync::MutexGuard<u64> = self.file_lock.lock().await;
+        let path: PathBuf = self.db.lock().unwrap().database_path().to_path_buf();
+        let (mut loaded, created, unreadable): (Database, bool, bool) = web::block(move || {
+            let (loaded, created): (Database, bool) = Database::load_or_create(&path);
+            let unreadable: bool = created && path.exists();
+            (loaded, created, unreadable)
+        })
+        .await
+        .map_err(|err| AppError::Internal(err.to_string()))?;
+        if unreadable {
+            return Err(AppError::Internal(format!(
+                "{} exists but could not be loaded, keeping the live database",
+                loaded.database_path().display()
+            )));
+        }
+        loaded.set_id_strategy(self.config.id_strategy);
+        if self.config.repair_on_load {
+            repair_loaded(&mut loaded);
+        }
+
+        let mut db: std::sync::MutexGuard<Database> = self.db.lock().unwrap();
+        *db = loaded;
+        // Snapshots taken before the reload would write the old data back over the file
+        *persisted = self.snapshot_generation.load(Ordering::SeqCst);
+        self.metrics.set_saved_generation(*persisted);
+        Ok(created)
+    }
+
+    // Move the database file under the persistence lock, so no save can land on the old path
+    // once the new file is written. The file is written from a snapshot off the async worker;
+    // the data lock is only held to take it and to switch the path. Returns the old path.
+    pub async fn relocate_database(&self, new_path: &Path) -> Result<PathBuf, AppError> {
+        let mut persisted: tokio::sync::MutexGuard<u64> = self.file_lock.lock().await;
+        // The data as of `generation`: every change with a snapshot up to there
+        let (old_path, data, generation): (PathBuf, Vec<u8>, u64) = {
+            let db: std::sync::MutexGuard<Database> = self.db.lock().unwrap();
+            if db.database_path() == new_path {
+                return Ok(new_path.to_path_buf());
+            }
+            let data: Vec<u8> = db.snapshot()?.data;
+            let generation: u64 = self.snapshot_generation.load(Ordering::SeqCst);
+            (db.database_path().to_path_buf(), data, generation)
+        };
+
+        let (from, to): (PathBuf, PathBuf) = (old_path.clone(), new_path.to_path_buf());
+        web::block(move || Database::move_file(&data, &from, &to))
+            .await
+            .map_err(|err| AppError::Internal(err.to_string()))?
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::AlreadyExists => AppError::Conflict(err.to_string()),
+                std::io::ErrorKind::NotFound => {
+                    AppError::BadRequest(format!("cannot write {}: {}", new_path.display(), err))
+                }
+                _ => AppError::from(err),
+            })?;
+
+        // Changes made while the file was being written are only in snapshots for the old
+        // path; write them to the new one before letting go of the persistence lock
+        let catch_up: Option<Snapshot> = {
+            let mut db: std::sync::MutexGuard<Database> = self.db.lock().unwrap();
+            db.set_database_path(new_path);
+            if self.snapshot_generation.load(Ordering::SeqCst) > generation {
+                Some(self.snapshot(&db)?)
+            } else {
+                None
+            }
+        };
+        *persisted = generation;
+        if let Some(catch_up) = catch_up {
+            catch_up
+                .write_with_retry(self.config.save_max_attempts)
+                .await?;
+            *persisted = catch_up.generation;
+        }
+        self.metrics.record_save(*persisted);
+        Ok(old_path)
+    }
+
+    // Write some other file (an export, say) under the persistence lock so it never races a
+    // save or a relocation, with the fs calls off the async worker
+    pub async fn write_data_file(&self, path: PathBuf, contents: Vec<u8>) -> std::io::Result<()> {
+        let _persisted: tokio::sync::MutexGuard<u64> = self.file_lock.lock().await;
+        web::block(move || std::fs::write(path, contents))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    // A snapshot was taken after the last one that reached disk
+    pub fn is_dirty(&self) -> bool {
+        self.snapshot_generation.load(Ordering::SeqCst) > self.metrics.saved_generation()
+    }
+
+    // Current values of every metric, for tests and monitoring code that would rather not
+    // parse the Prometheus text
+    pub fn metrics(&self) -> MetricsSnapshot {
+        let pair_count: usize = self.db.lock().unwrap().len();
+        MetricsSnapshot {
+            request_count: self.metrics.request_count(),
+            error_count: self.metrics.error_count(),
+            avg_latency_ms: self.metrics.avg_latency_ms(),
+            pair_count,
+            dirty: self.is_dirty(),
+            last_save: self.metrics.last_save(),
+            routes: self.metrics.route_latencies(),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    // POST a startup or shutdown event to the configured webhook. False when none is configured
+    // or the send failed; either way the wait is bounded by config.webhook_timeout.
+    pub async fn notify_lifecycle(&self, event: ForexPairEvent) -> bool {
+        match (&self.config.webhook_url, self.config.webhook_enabled) {
+            (Some(url), true) => {
+                webhook::notify(&self.http_client, url, self.config.webhook_timeout, &event).await
+            }
+            _ => false,
+        }
+    }
+
+    // Swap in a different price source, e.g. a stub in tests
+    pub fn with_provider(mut self, provider: Arc<dyn PriceProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    // Record an event in the audit log and fan it out to subscribers
+    pub fn publish(&self, event: ForexPairEvent) {
+        self.count_price_change(&event);
+        self.audit.record(event.clone());
+        // No receivers is fine, nobody is waiting
+        let _ = self.events.send(event);
+    }
+
+    fn count_price_change(&self, event: &ForexPairEvent) {
+        match &event.data {
+            ForexPairEventData::Updated { before, after } if before.price != after.price => {
+                self.metrics.record_price_change(after.into());
+            }
+            // A patch only names the fields, the labels come from the stored record
+            ForexPairEventData::Patched { changes }
+                if changes.iter().any(|diff| diff.field == "price") =>
+            {
+                if let Some(forex_pair) = self.db.lock().unwrap().get(&event.pair_id) {
+                    self.metrics.record_price_change(forex_pair.into());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Take a write permit without waiting; a full backlog is reported as 503 straight away
+    pub fn try_acquire_write(&self) -> Result<WritePermit<'_>, AppError> {
+        let permit: SemaphorePermit = self.write_semaphore.try_acquire().map_err(|_| {
+            AppError::ServiceUnavailable("too many concurrent writes, try again".to_string())
+        })?;
+        self.metrics.write_started();
+        Ok(WritePermit {
+            _permit: permit,
+            metrics: &self.metrics,
+        })
+    }
+}
+
+// The repaired data stays in memory and reaches the file with the next save
+fn repair_loaded(db: &mut Database) {
+    let report: RepairReport = db.repair();
+    if !report.is_empty() {
+        tracing::warn!(
+            path = %db.database_path().display(),
+            rekeyed = ?report.rekeyed_ids,
+            quarantined = ?report.quarantined_ids,
+            orphaned_history = ?report.orphaned_history_ids,
+            index_rebuilt = report.index_rebuilt,
+            "repaired inconsistent database"
+        );
+    }
+}
+
+// Header names and values checked up front so a typo fails at startup, not per response
+pub fn parse_custom_headers(
+    headers: &HashMap<String, String>,
+) -> Result<Vec<(HeaderName, HeaderValue)>, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name: HeaderName = name
+                .parse()
+                .map_err(|_| format!("{:?} is not a valid header name", name))?;
+            let value: HeaderValue = value
+                .parse()
+                .map_err(|_| format!("{:?} is not a valid value for {}", value, name))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+// Held for the whole critical section of a mutating handler, including the save
+pub struct WritePermit<'a> {
+    _permit: SemaphorePermit<'a>,
+    metrics: &'a Metrics,
+}
+
+impl Drop for WritePermit<'_> {
+    fn drop(&mut self) {
+        self.metrics.write_finished();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::test_support::{forex_pair, temp_database, temp_dir, test_state};
+    use actix_web::web;
+    use std::thread::JoinHandle;
+
+    // Saves and reloads from several threads at once: every reload must find a whole file
+    #[test]
+    fn tests_concurrent_saves_and_reloads_keep_the_file_valid() {
+        let mut db: Database = temp_database("file_lock");
+        for id in 1..=200 {
+            db.insert(forex_pair(id, &format!("P{:03}/USD", id), 1.0 + id as f64));
+        }
+        db.save_to_file().unwrap();
+        let path: PathBuf = db.database_path().to_path_buf();
+        let state: web::Data<AppState> = test_state(db, Config::default());
+
+        let spawn = |reload: bool| -> JoinHandle<()> {
+            let state: web::Data<AppState> = state.clone();
+            std::thread::spawn(move || {
+                let runtime: tokio::runtime::Runtime =
+                    tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap();
+                runtime.block_on(async {
+                    for _ in 0..25 {
+                        if reload {
+                            assert!(!state.reload().await.unwrap());
+                        } else {
+                            let snapshot: Snapshot = {
+                                let db = state.db.lock().unwrap();
+                                state.snapshot(&db).unwrap()
+                            };
+                            state.persist(snapshot).await.unwrap();
+                        }
+                    }
+                })
+            })
+        };
+        let threads: Vec<JoinHandle<()>> = [false, true, false, true, false, true]
+            .into_iter()
+            .map(spawn)
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(Database::load_from_file(&path).unwrap().len(), 200);
+        assert_eq!(state.db.lock().unwrap().len(), 200);
+    }
+
+    // A file from an older version: record 7 claims id 2, so the pair index built from the
+    // keys disagrees with the records, and EUR/USD is stored twice
+    #[test]
+    fn tests_inconsistent_database_is_repaired_on_load() {
+        let path: PathBuf = temp_dir("repair_on_load").join("database.json");
+        let legacy: serde_json::Value = serde_json::json!({
+            "forex_pairs": {
+                "1": { "id": 1, "pair": "EUR/USD", "price": 1.08 },
+                "3": { "id": 3, "pair": "EUR/USD", "price": 1.09 },
+                "7": { "id": 2, "pair": "GBP/USD", "price": 1.27 }
+            },
+            "price_history": {
+                "9": [{ "timestamp": "2024-01-01T00:00:00Z", "price": 1.5 }]
+            }
+        });
+        std::fs::write(&path, legacy.to_string()).unwrap();
+
+        let (db, _created): (Database, bool) = Database::load_or_create(&path);
+        assert!(db.check_integrity().is_err());
+        let state: web::Data<AppState> = test_state(db, Config::default());
+
+        let db = state.db.lock().unwrap();
+        assert!(db.check_integrity().is_ok());
+        assert_eq!(db.len(), 2);
+        assert_eq!(db.find_by_pair("GBP/USD").unwrap().id, 7);
+        assert_eq!(db.find_by_pair("EUR/USD").unwrap().id, 1);
+        assert_eq!(
+            db.quarantine().keys().copied().collect::<Vec<u64>>(),
+            vec![3]
+        );
+        assert!(db.price_history(&9).is_empty());
+    }
+}
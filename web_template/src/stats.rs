@@ -0,0 +1,172 @@
+use crate::database::{Database, ForexPair, PricePoint};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PercentileField {
+    Price,
+    // Bid/ask spread as a percentage of mid; pairs without a quote are left out
+    Spread,
+    // Change against the last price at least 24h old (or the oldest one we have)
+    DailyChangePct,
+}
+
+impl Database {
+    // Linear interpolation between closest ranks (P50 of an even count is the mean of the two
+    // middle values). select_nth_unstable keeps this O(n) instead of sorting everything.
+    pub fn percentile(&self, field: PercentileField, p: f64) -> Option<Decimal> {
+        if !(p > 0.0 && p <= 100.0) {
+            return None;
+        }
+        let now: DateTime<Utc> = Utc::now();
+        let mut values: Vec<Decimal> = self
+            .iter()
+            .filter_map(|forex_pair| match field {
+                PercentileField::Price => Decimal::from_f64(forex_pair.price),
+                PercentileField::Spread => forex_pair.spread_pct(),
+                PercentileField::DailyChangePct => self
+                    .daily_change_pct(forex_pair, now)
+                    .and_then(Decimal::from_f64),
+            })
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+
+        let rank: Decimal = Decimal::from_f64(p / 100.0)? * Decimal::from(values.len() - 1);
+        let lower: usize = rank.floor().try_into().ok()?;
+        let fraction: Decimal = rank - rank.floor();
+        let (_, low, above) = values.select_nth_unstable(lower);
+        let low: Decimal = *low;
+        if fraction.is_zero() {
+            return Some(low.normalize());
+        }
+        // The next rank up is the smallest value in the upper partition
+        let high: Decimal = above.iter().copied().min()?;
+        Some((low + (high - low) * fraction).normalize())
+    }
+
+    pub fn daily_change_pct(&self, forex_pair: &ForexPair, now: DateTime<Utc>) -> Option<f64> {
+        let history: &[PricePoint] = self.price_history(&forex_pair.id);
+        let cutoff: DateTime<Utc> = now - Duration::hours(24);
+        let reference: &PricePoint = history
+            .iter()
+            .rev()
+            .find(|point| point.timestamp <= cutoff)
+            .or_else(|| history.first())?;
+        if reference.price <= 0.0 {
+            return None;
+        }
+        Some((forex_pair.price - reference.price) / reference.price * 100.0)
+    }
+
+    // Count prices into `bins` equal-width buckets spanning [min, max]. Buckets are
+    // half-open [start, end) except the last, which includes max; prices outside the range
+    // are not counted. Callers check bins >= 1 and min < max.
+    pub fn heatmap(&self, bins: usize, min: f64, max: f64) -> Vec<HeatmapBucket> {
+        let width: f64 = (max - min) / bins as f64;
+        let mut buckets: Vec<HeatmapBucket> = (0..bins)
+            .map(|index| HeatmapBucket {
+                range_start: min + width * index as f64,
+                range_end: if index + 1 == bins {
+                    max
+                } else {
+                    min + width * (index + 1) as f64
+                },
+                count: 0,
+            })
+            .collect();
+        for forex_pair in self.iter() {
+            if !(min..=max).contains(&forex_pair.price) {
+                continue;
+            }
+            let index: usize = (((forex_pair.price - min) / width) as usize).min(bins - 1);
+            buckets[index].count += 1;
+        }
+        buckets
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HeatmapBucket {
+    pub range_start: f64,
+    pub range_end: f64,
+    pub count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::forex_pair;
+    use std::str::FromStr;
+
+    fn prices(prices: &[f64]) -> Database {
+        let mut db: Database = Database::new();
+        for (index, price) in prices.iter().enumerate() {
+            let pair: String = format!("AA{}/USD", (b'A' + index as u8) as char);
+            db.insert(forex_pair(index as u64 + 1, &pair, *price));
+        }
+        db
+    }
+
+    #[test]
+    fn tests_p50_is_the_median() {
+        let odd: Database = prices(&[1.3, 0.9, 151.2, 1.1, 0.6]);
+        assert_eq!(
+            odd.percentile(PercentileField::Price, 50.0),
+            Decimal::from_str("1.1").ok()
+        );
+
+        let even: Database = prices(&[1.3, 0.9, 151.2, 1.1]);
+        assert_eq!(
+            even.percentile(PercentileField::Price, 50.0),
+            Decimal::from_str("1.2").ok()
+        );
+
+        assert_eq!(
+            even.percentile(PercentileField::Price, 100.0),
+            Decimal::from_str("151.2").ok()
+        );
+        assert_eq!(even.percentile(PercentileField::Price, 0.0), None);
+        assert_eq!(
+            Database::new().percentile(PercentileField::Price, 50.0),
+            None
+        );
+    }
+
+    #[test]
+    fn tests_heatmap_counts_prices_in_range() {
+        let db: Database = prices(&[0.5, 0.6, 0.7, 1.1, 1.3, 1.99, 2.0, 0.4, 151.2]);
+        let buckets: Vec<HeatmapBucket> = db.heatmap(3, 0.5, 2.0);
+        let counts: Vec<usize> = buckets.iter().map(|bucket| bucket.count).collect();
+        // [0.5, 1.0) [1.0, 1.5) [1.5, 2.0]; 0.4 and 151.2 are outside
+        assert_eq!(counts, vec![3, 2, 2]);
+        assert_eq!(counts.iter().sum::<usize>(), 7);
+        assert_eq!(buckets[0].range_start, 0.5);
+        assert_eq!(buckets[2].range_end, 2.0);
+        assert_eq!(buckets[0].range_end, buckets[1].range_start);
+    }
+
+    #[test]
+    fn tests_daily_change_uses_price_from_a_day_ago() {
+        let now: DateTime<Utc> = Utc::now();
+        let mut db: Database = Database::new();
+        db.record_price(1, 1.0, now - Duration::hours(30));
+        db.record_price(1, 1.05, now - Duration::hours(25));
+        db.record_price(1, 1.2, now - Duration::hours(2));
+        let current: ForexPair = forex_pair(1, "EUR/USD", 1.155);
+        db.insert(current.clone());
+
+        let change: f64 = db.daily_change_pct(&current, now).unwrap();
+        assert!((change - 10.0).abs() < 1e-9, "{}", change);
+        assert_eq!(
+            db.percentile(PercentileField::DailyChangePct, 50.0)
+                .map(|value| value.round_dp(6)),
+            Decimal::from_str("10").ok()
+        );
+        assert_eq!(db.percentile(PercentileField::Spread, 50.0), None);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::config::Config;
+use crate::database::{Database, ForexPair};
+use crate::middleware::new_app;
+use crate::routes;
+use crate::state::AppState;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{http::header, test, web};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+
+pub fn admin_config() -> Config {
+    Config {
+        admin_token: Some(ADMIN_TOKEN.to_string()),
+        ..Config::default()
+    }
+}
+
+pub fn admin_header() -> (header::HeaderName, String) {
+    (header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN))
+}
+
+// Fresh directory under the system temp dir so tests never touch ./database.json
+pub fn temp_dir(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let nanos: u128 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir: PathBuf = std::env::temp_dir().join(format!(
+        "web_template_{}_{}_{}_{}",
+        name,
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn temp_database(name: &str) -> Database {
+    Database::with_path(temp_dir(name).join("database.json"))
+}
+
+pub fn test_state(db: Database, config: Config) -> web::Data<AppState> {
+    web::Data::new(AppState::new(db, config))
+}
+
+// Build the app the same way main does, minus CORS
+pub async fn init_app(
+    state: web::Data<AppState>,
+) -> impl Service<
+    actix_http::Request,
+    Response = ServiceResponse<impl MessageBody>,
+    Error = actix_web::Error,
+> {
+    let config: Config = state.config.clone();
+    test::init_service(
+        new_app()
+            .app_data(state)
+            .configure(move |cfg| routes::configure(cfg, &config)),
+    )
+    .await
+}
+
+pub fn forex_pair(id: u64, pair: &str, price: f64) -> ForexPair {
+    ForexPair {
+        id,
+        pair: pair.to_string(),
+        price,
+        bid: None,
+        ask: None,
+        created_at: None,
+        extra_fields: None,
+    }
+}
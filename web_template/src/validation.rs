@@ -0,0 +1,130 @@
+use crate::database::ForexPair;
+use crate::error::AppError;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use std::fmt;
+
+pub const DEFAULT_MAX_SPREAD_PCT: Decimal = Decimal::ONE;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    InvalidPrice(f64),
+    CrossedQuote { bid: f64, ask: f64 },
+    SpreadTooWide { actual: Decimal, maximum: Decimal },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPrice(price) => write!(f, "price {} must be a positive number", price),
+            Self::CrossedQuote { bid, ask } => {
+                write!(
+                    f,
+                    "bid {} and ask {} must be positive with bid <= ask",
+                    bid, ask
+                )
+            }
+            Self::SpreadTooWide { actual, maximum } => write!(
+                f,
+                "spread of {}% exceeds the maximum of {}%",
+                actual.round_dp(4).normalize(),
+                maximum.normalize()
+            ),
+        }
+    }
+}
+
+impl From<ValidationError> for AppError {
+    fn from(err: ValidationError) -> Self {
+        AppError::BadRequest(err.to_string())
+    }
+}
+
+impl ForexPair {
+    // Reject prices a sane feed would never send. Spread is (ask - bid) / mid * 100.
+    pub fn validate(&self, max_spread_pct: Decimal) -> Result<(), ValidationError> {
+        if !self.price.is_finite() || self.price <= 0.0 {
+            return Err(ValidationError::InvalidPrice(self.price));
+        }
+
+        if let (Some(bid), Some(ask)) = (self.bid, self.ask) {
+            if !(bid.is_finite() && ask.is_finite()) || bid <= 0.0 || bid > ask {
+                return Err(ValidationError::CrossedQuote { bid, ask });
+            }
+            let actual: Decimal = self.spread_pct().unwrap_or_default();
+            if actual > max_spread_pct {
+                return Err(ValidationError::SpreadTooWide {
+                    actual,
+                    maximum: max_spread_pct,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Bid/ask spread as a percentage of the mid price, when both sides are quoted. Worked out in
+    // Decimal so a spread right at the limit is not tipped over it by float rounding.
+    pub fn spread_pct(&self) -> Option<Decimal> {
+        let bid: Decimal = Decimal::from_f64(self.bid?)?;
+        let ask: Decimal = Decimal::from_f64(self.ask?)?;
+        let mid: Decimal = (bid + ask) / Decimal::TWO;
+        if mid > Decimal::ZERO {
+            Some((ask - bid) / mid * Decimal::ONE_HUNDRED)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::forex_pair;
+    use std::str::FromStr;
+
+    fn quoted(bid: f64, ask: f64) -> ForexPair {
+        let mut quoted: ForexPair = forex_pair(1, "EUR/USD", (bid + ask) / 2.0);
+        quoted.bid = Some(bid);
+        quoted.ask = Some(ask);
+        quoted
+    }
+
+    #[test]
+    fn tests_validate_spread() {
+        // 50% spread: bid 0.8, ask 1.333.. around a mid of 1.0666..
+        let wide: ForexPair = quoted(0.8, 1.333_333);
+        match wide.validate(DEFAULT_MAX_SPREAD_PCT) {
+            Err(ValidationError::SpreadTooWide { actual, maximum }) => {
+                assert_eq!(actual.round_dp(2), Decimal::from(50));
+                assert_eq!(maximum, Decimal::ONE);
+            }
+            other => panic!("expected SpreadTooWide, got {:?}", other),
+        }
+
+        // 0.01% spread
+        let tight: ForexPair = quoted(1.08, 1.080_108);
+        assert!(tight.validate(DEFAULT_MAX_SPREAD_PCT).is_ok());
+
+        // Exactly at the limit passes: 0.01 / 1.0 * 100 is 1% with no float error
+        let at_limit: ForexPair = quoted(0.995, 1.005);
+        assert_eq!(at_limit.spread_pct(), Decimal::from_str("1").ok());
+        assert!(at_limit.validate(DEFAULT_MAX_SPREAD_PCT).is_ok());
+
+        // No quote at all is fine
+        assert!(forex_pair(1, "EUR/USD", 1.08)
+            .validate(Decimal::ZERO)
+            .is_ok());
+    }
+
+    #[test]
+    fn tests_validate_rejects_bad_prices() {
+        assert_eq!(
+            forex_pair(1, "EUR/USD", 0.0).validate(DEFAULT_MAX_SPREAD_PCT),
+            Err(ValidationError::InvalidPrice(0.0))
+        );
+        assert!(matches!(
+            quoted(1.1, 1.0).validate(DEFAULT_MAX_SPREAD_PCT),
+            Err(ValidationError::CrossedQuote { .. })
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+use crate::events::ForexPairEvent;
+use reqwest::Client as HttpClient;
+use std::time::Duration;
+
+// Short on purpose: a slow receiver must not hold up startup or shutdown
+pub const DEFAULT_WEBHOOK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// POST the event as JSON, in the same shape the audit log keeps. Best effort: errors, non-2xx
+// answers and timeouts are logged and reported as false, never returned, so callers can await
+// this without failing
+pub async fn notify(
+    client: &HttpClient,
+    url: &str,
+    timeout: Duration,
+    event: &ForexPairEvent,
+) -> bool {
+    match client.post(url).timeout(timeout).json(event).send().await {
+        Ok(resp) if resp.status().is_success() => true,
+        Ok(resp) => {
+            tracing::warn!(url, status = %resp.status(), "webhook refused event");
+            false
+        }
+        Err(err) => {
+            tracing::warn!(url, error = %err, "could not send event to webhook");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::database::Database;
+    use crate::state::AppState;
+    use crate::test_support::{forex_pair, temp_database};
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use serde_json::Value;
+    use std::net::SocketAddr;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    // Raw bodies, so tests can compare the exact bytes sent
+    type Received = web::Data<Mutex<Vec<web::Bytes>>>;
+
+    async fn receive(received: Received, body: web::Bytes) -> HttpResponse {
+        received.lock().unwrap().push(body);
+        HttpResponse::NoContent().finish()
+    }
+
+    fn parsed(received: &Received) -> Vec<Value> {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|body| serde_json::from_slice(body).unwrap())
+            .collect()
+    }
+
+    // A webhook receiver on a free port, recording every body it gets
+    fn mock_receiver() -> (SocketAddr, Received) {
+        let received: Received = web::Data::new(Mutex::new(vec![]));
+        let app_received: Received = received.clone();
+        let server: HttpServer<_, _, _, _> = HttpServer::new(move || {
+            App::new()
+                .app_data(app_received.clone())
+                .route("/hook", web::post().to(receive))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr: SocketAddr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        (addr, received)
+    }
+
+    #[actix_web::test]
+    async fn tests_startup_and_shutdown_events_reach_the_webhook() {
+        let (addr, received): (SocketAddr, Received) = mock_receiver();
+        let mut db: Database = temp_database("webhook");
+        db.insert(forex_pair(1, "EUR/USD", 1.08));
+        let config: Config = Config {
+            webhook_url: Some(format!("http://{}/hook", addr)),
+            ..Config::default()
+        };
+        let state: AppState = AppState::new(db, config);
+
+        assert!(
+            state
+                .notify_lifecycle(ForexPairEvent::startup(1, "127.0.0.1:8080"))
+                .await
+        );
+        assert!(
+            state
+                .notify_lifecycle(ForexPairEvent::shutdown(state.uptime().as_secs(), 1))
+                .await
+        );
+
+        let received: Vec<Value> = parsed(&received);
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0]["event_type"], "startup");
+        assert_eq!(received[0]["actor"], "system");
+        let startup: &Value = &received[0]["data"]["startup"];
+        assert_eq!(startup["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(startup["pair_count"], 1);
+        assert_eq!(startup["bind_address"], "127.0.0.1:8080");
+        assert_eq!(received[1]["event_type"], "shutdown");
+        assert_eq!(received[1]["data"]["shutdown"]["pair_count"], 1);
+        assert!(received[1]["data"]["shutdown"]["uptime_secs"].is_u64());
+
+        // The same envelope the audit log keeps for pair changes
+        let audited: Value = serde_json::to_value(ForexPairEvent::created(
+            "admin",
+            &forex_pair(1, "EUR/USD", 1.08),
+        ))
+        .unwrap();
+        let keys = |value: &Value| -> Vec<String> {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        for event in &received {
+            assert_eq!(keys(event), keys(&audited));
+            serde_json::from_value::<ForexPairEvent>(event.clone()).unwrap();
+        }
+    }
+
+    #[actix_web::test]
+    async fn tests_webhook_failures_are_not_errors() {
+        let (addr, received): (SocketAddr, Received) = mock_receiver();
+        let disabled: AppState = AppState::new(
+            Database::new(),
+            Config {
+                webhook_url: Some(format!("http://{}/hook", addr)),
+                webhook_enabled: false,
+                ..Config::default()
+            },
+        );
+        assert!(
+            !disabled
+                .notify_lifecycle(ForexPairEvent::startup(0, ""))
+                .await
+        );
+        assert!(received.lock().unwrap().is_empty());
+
+        // Nothing listens on port 9 (discard) here: the send fails fast and only logs
+        let unreachable: AppState = AppState::new(
+            Database::new(),
+            Config {
+                webhook_url: Some("http://127.0.0.1:9/hook".to_string()),
+                webhook_timeout: Duration::from_millis(200),
+                ..Config::default()
+            },
+        );
+        let started: Instant = Instant::now();
+        assert!(
+            !unreachable
+                .notify_lifecycle(ForexPairEvent::startup(0, ""))
+                .await
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    // One event as the audit log keeps it, as long-poll waiters get it from the broadcast
+    // channel and as the webhook receives it: the same bytes every time
+    #[actix_web::test]
+    async fn tests_event_json_is_identical_for_every_consumer() {
+        let (addr, received): (SocketAddr, Received) = mock_receiver();
+        let url: String = format!("http://{}/hook", addr);
+        let state: AppState = AppState::new(Database::new(), Config::default());
+        let mut subscriber: tokio::sync::broadcast::Receiver<ForexPairEvent> =
+            state.events.subscribe();
+
+        for event in [
+            ForexPairEvent::created("admin", &forex_pair(1, "EUR/USD", 1.08)),
+            ForexPairEvent::market_open("scheduler", "London"),
+            ForexPairEvent::market_close("scheduler", "London"),
+        ] {
+            state.publish(event.clone());
+            let audited: Vec<u8> =
+                serde_json::to_vec(state.audit.entries().last().unwrap()).unwrap();
+            let broadcast: Vec<u8> = serde_json::to_vec(&subscriber.recv().await.unwrap()).unwrap();
+            assert!(notify(&HttpClient::new(), &url, DEFAULT_WEBHOOK_TIMEOUT, &event).await);
+            let sent: web::Bytes = received.lock().unwrap().last().unwrap().clone();
+
+            assert_eq!(audited, broadcast);
+            assert_eq!(audited, sent.to_vec());
+        }
+        let types: Vec<Value> = parsed(&received)
+            .iter()
+            .map(|event| event["event_type"].clone())
+            .collect();
+        assert_eq!(types, ["created", "market_open", "market_close"]);
+    }
+}